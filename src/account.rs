@@ -0,0 +1,86 @@
+use chrono::{Local, NaiveDateTime};
+use indicatif::HumanBytes;
+
+use crate::{
+    eyre,
+    remote::Remote,
+    storage::{RemoteAccount, RemoteStorage},
+    Result,
+};
+
+pub struct Account {}
+
+/// Uptobox account
+impl Account {
+    /// Check if the account is premium, warning when it expires soon
+    pub async fn check(storage: &dyn RemoteStorage, warning_days: i64) -> Result<()> {
+        let account = storage
+            .get_account()
+            .await
+            .map_err(|e| eyre!("Unable to retreive your uptobox account ({e})"))?;
+        if !account.premium {
+            return Err(eyre!("A premium account is needed to use this software"));
+        }
+
+        if let Some(days) = Self::days_left(&account) {
+            if days <= warning_days {
+                eprintln!(
+                    "Warning: the premium subscription expires in {days} day(s) ({})",
+                    account.premium_expire
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Print the account status
+    pub async fn show(storage: &dyn RemoteStorage, quota: u64) -> Result<()> {
+        let account = storage
+            .get_account()
+            .await
+            .map_err(|e| eyre!("Unable to retreive your uptobox account ({e})"))?;
+        let used = Self::used_storage(storage).await?;
+
+        println!("Username:       {}", account.login);
+        println!("Email:          {}", account.email);
+        match (account.premium, Self::days_left(&account)) {
+            (true, Some(days)) => println!(
+                "Premium:        until {} ({days} day(s) left)",
+                account.premium_expire
+            ),
+            (true, None) => println!("Premium:        yes"),
+            (false, _) => println!("Premium:        no"),
+        }
+        println!("Points:         {}", account.point);
+        println!("Storage used:   {}", HumanBytes(used));
+        if quota > 0 {
+            let quota = quota * 1024 * 1024 * 1024;
+            println!("Storage left:   {}", HumanBytes(quota.saturating_sub(used)));
+        }
+        Ok(())
+    }
+
+    /// Days left before the premium expires
+    fn days_left(account: &RemoteAccount) -> Option<i64> {
+        let expire =
+            NaiveDateTime::parse_from_str(&account.premium_expire, "%Y-%m-%d %H:%M:%S").ok()?;
+        Some((expire - Local::now().naive_local()).num_days())
+    }
+
+    /// Size of every file of the account
+    async fn used_storage(storage: &dyn RemoteStorage) -> Result<u64> {
+        let mut used = 0;
+        let mut folders = vec![String::from("//")];
+        while let Some(folder) = folders.pop() {
+            let listing = storage.list(&folder).await?;
+            used += listing.files.iter().map(|f| f.file_size).sum::<u64>();
+            folders.extend(
+                listing
+                    .folders
+                    .iter()
+                    .map(|f| Remote::join(&folder, &Remote::folder_name(f))),
+            );
+        }
+        Ok(used)
+    }
+}
@@ -0,0 +1,270 @@
+use async_trait::async_trait;
+use regex::Regex;
+use reqwest::{
+    multipart::{Form, Part},
+    Body, Client, Method, RequestBuilder,
+};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+
+use crate::{
+    eyre,
+    storage::{RemoteAccount, RemoteFile, RemoteFolder, RemoteListing, RemoteStorage},
+    Result,
+};
+
+/// Number of items per page of the listing endpoint
+const PAGE_LIMIT: usize = 100;
+
+/// Client of the Uptobox API
+pub struct UptoboxApi {
+    client: Client,
+    token: String,
+    url: String,
+}
+
+impl UptoboxApi {
+    pub fn new(client: Client, token: &str, url: &str) -> Self {
+        Self {
+            client,
+            token: token.to_string(),
+            url: url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// Get the upload link, relative links use the scheme of the API
+    async fn get_upload_url(&self) -> Result<String> {
+        let link: RemoteUploadLink = self
+            .request(self.client.get(self.endpoint("upload")))
+            .await?;
+        match link.upload_link.strip_prefix("//") {
+            Some(link) => {
+                let scheme = self.url.split("://").next().unwrap_or("https");
+                Ok(format!("{scheme}://{link}"))
+            }
+            None => Ok(link.upload_link),
+        }
+    }
+
+    /// Url of an endpoint
+    fn endpoint(&self, endpoint: &str) -> String {
+        format!("{}/{endpoint}", self.url)
+    }
+
+    /// Send a modification of the files or folders
+    async fn edit(&self, method: Method, mut body: Value) -> Result<()> {
+        body["token"] = Value::from(self.token.as_str());
+        let res: ApiResponse<Value> = self
+            .client
+            .request(method, self.endpoint("user/files"))
+            .json(&body)
+            .send()
+            .await?
+            .json()
+            .await?;
+        match res.status_code {
+            0 => Ok(()),
+            _ => Err(eyre!("{}", res.message)),
+        }
+    }
+
+    /// Send a request and extract the data of the response
+    async fn request<T: DeserializeOwned>(&self, request: RequestBuilder) -> Result<T> {
+        let res: ApiResponse<T> = request
+            .query(&[("token", &self.token)])
+            .send()
+            .await?
+            .json()
+            .await?;
+        match (res.status_code, res.data) {
+            (0, Some(data)) => Ok(data),
+            _ => Err(eyre!("{}", res.message)),
+        }
+    }
+}
+
+#[async_trait]
+impl RemoteStorage for UptoboxApi {
+    fn file_code(&self, link: &str) -> Result<String> {
+        let regex = Regex::new(
+            r#"https://(?:uptobox|uptostream).[a-zA-Z]+/(?P<file_code>[a-zA-Z0-9]{12})"#,
+        )?;
+        Ok(regex
+            .captures(link)
+            .ok_or_else(|| eyre!("Unable to parse the file code for '{link}' (regex: https://(?:uptobox|uptostream).[a-zA-Z]+/(?P<file_code>[a-zA-Z0-9]{{12}}) )"))?
+            .name("file_code")
+            .ok_or_else(|| eyre!("Unable to parse the file code for '{link}' (regex: https://(?:uptobox|uptostream).[a-zA-Z]+/(?P<file_code>[a-zA-Z0-9]{{12}}) )"))?
+            .as_str()
+            .to_string())
+    }
+
+    async fn get_file(&self, file_code: &str) -> Result<RemoteFile> {
+        let infos: RemoteFileInfos = self
+            .request(
+                self.client
+                    .get(self.endpoint("link/info"))
+                    .query(&[("fileCodes", file_code)]),
+            )
+            .await?;
+        let info = infos
+            .list
+            .into_iter()
+            .next()
+            .ok_or_else(|| eyre!("Unable to find '{file_code}' on uptobox"))?;
+        Ok(RemoteFile {
+            file_code: file_code.to_string(),
+            file_name: info.file_name,
+            file_size: info.file_size,
+            file_created: String::new(),
+        })
+    }
+
+    async fn get_download_url(&self, file_code: &str) -> Result<String> {
+        let link: RemoteDownloadLink = self
+            .request(
+                self.client
+                    .get(self.endpoint("link"))
+                    .query(&[("file_code", file_code)]),
+            )
+            .await?;
+        link.dl_link
+            .ok_or_else(|| eyre!("A premium account is needed to use this software"))
+    }
+
+    async fn upload(&self, body: Body, name: &str) -> Result<()> {
+        let url = self.get_upload_url().await?;
+        let file = Part::stream(body)
+            .file_name(name.to_owned())
+            .mime_str("video/x-matroska")?;
+        let form = Form::new().part("file", file);
+
+        let res = self.client.post(url).multipart(form).send().await?;
+        match res.status().is_success() {
+            true => Ok(()),
+            false => Err(eyre!("Unknown error (status: {})", res.status())),
+        }
+    }
+
+    /// List a remote folder with every page of its files
+    async fn list(&self, path: &str) -> Result<RemoteListing> {
+        let mut offset = 0;
+        let mut listing: Option<RemoteListing> = None;
+        loop {
+            let page: RemoteListing = self
+                .request(
+                    self.client
+                        .get(self.endpoint("user/files"))
+                        .query(&[("path", path)])
+                        .query(&[("limit", PAGE_LIMIT), ("offset", offset)]),
+                )
+                .await
+                .map_err(|e| eyre!("Unable to list '{path}' on uptobox ({e})"))?;
+
+            let count = page.files.len();
+            match listing.as_mut() {
+                Some(listing) => listing.files.extend(page.files),
+                None => listing = Some(page),
+            }
+            if count < PAGE_LIMIT {
+                break;
+            }
+            offset += PAGE_LIMIT;
+        }
+        listing.ok_or_else(|| eyre!("Unable to list '{path}' on uptobox"))
+    }
+
+    async fn create_folder(&self, path: &str, name: &str) -> Result<()> {
+        self.edit(Method::PUT, json!({ "path": path, "name": name }))
+            .await
+            .map_err(|e| eyre!("Unable to create the folder '{name}' in '{path}' ({e})"))
+    }
+
+    async fn move_files(&self, file_codes: &[&str], destination: &RemoteFolder) -> Result<()> {
+        self.edit(
+            Method::PATCH,
+            json!({ "file_codes": file_codes.join(","), "destination_fld_id": destination.fld_id, "action": "move" }),
+        )
+        .await
+        .map_err(|e| eyre!("Unable to move the files on uptobox ({e})"))
+    }
+
+    async fn move_folder(&self, folder: &RemoteFolder, destination: &RemoteFolder) -> Result<()> {
+        self.edit(
+            Method::PATCH,
+            json!({ "fld_id": folder.fld_id, "destination_fld_id": destination.fld_id, "action": "move" }),
+        )
+        .await
+        .map_err(|e| eyre!("Unable to move the folder on uptobox ({e})"))
+    }
+
+    async fn rename_file(&self, file_code: &str, name: &str) -> Result<()> {
+        self.edit(
+            Method::PATCH,
+            json!({ "file_code": file_code, "new_name": name }),
+        )
+        .await
+        .map_err(|e| eyre!("Unable to rename '{file_code}' to '{name}' ({e})"))
+    }
+
+    async fn rename_folder(&self, folder: &RemoteFolder, name: &str) -> Result<()> {
+        self.edit(
+            Method::PATCH,
+            json!({ "fld_id": folder.fld_id, "new_name": name }),
+        )
+        .await
+        .map_err(|e| eyre!("Unable to rename the folder to '{name}' ({e})"))
+    }
+
+    async fn delete_files(&self, file_codes: &[&str]) -> Result<()> {
+        self.edit(
+            Method::DELETE,
+            json!({ "file_codes": file_codes.join(",") }),
+        )
+        .await
+        .map_err(|e| eyre!("Unable to delete the files on uptobox ({e})"))
+    }
+
+    async fn delete_folder(&self, folder: &RemoteFolder) -> Result<()> {
+        self.edit(Method::DELETE, json!({ "fld_id": folder.fld_id }))
+            .await
+            .map_err(|e| eyre!("Unable to delete the folder on uptobox ({e})"))
+    }
+
+    async fn get_account(&self) -> Result<RemoteAccount> {
+        self.request(self.client.get(self.endpoint("user/me")))
+            .await
+    }
+}
+
+#[derive(Deserialize)]
+struct ApiResponse<T> {
+    #[serde(rename = "statusCode")]
+    status_code: i64,
+    #[serde(default)]
+    message: String,
+    data: Option<T>,
+}
+
+#[derive(Deserialize, Debug)]
+struct RemoteFileInfos {
+    list: Vec<RemoteFileInfo>,
+}
+
+#[derive(Deserialize, Debug)]
+struct RemoteFileInfo {
+    file_name: String,
+    #[serde(default)]
+    file_size: u64,
+}
+
+#[derive(Deserialize, Debug)]
+struct RemoteDownloadLink {
+    #[serde(rename = "dlLink")]
+    dl_link: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct RemoteUploadLink {
+    #[serde(rename = "uploadLink")]
+    upload_link: String,
+}
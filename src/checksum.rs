@@ -0,0 +1,49 @@
+use sha2::{Digest, Sha256};
+use std::{
+    fs::{self, File},
+    io,
+    path::Path,
+};
+
+use crate::{eyre, Result};
+
+pub struct Checksum {}
+
+/// SHA-256 checksums of the local files
+impl Checksum {
+    /// Hash a local file
+    pub fn file(path: &str) -> Result<String> {
+        let mut file =
+            File::open(path).map_err(|e| eyre!("Unable to open the file '{path}' ({e})"))?;
+        let mut hasher = Sha256::new();
+        io::copy(&mut file, &mut hasher)
+            .map_err(|e| eyre!("Unable to hash the file '{path}' ({e})"))?;
+        Ok(format!("{:x}", hasher.finalize()))
+    }
+
+    /// Write the checksum next to the file (sha256sum format)
+    pub fn write_sidecar(path: &str, hash: &str) -> Result<()> {
+        let name = Path::new(path)
+            .file_name()
+            .ok_or_else(|| eyre!("Unable to extract file name"))?
+            .to_string_lossy();
+        fs::write(Self::sidecar_path(path), format!("{hash}  {name}\n"))
+            .map_err(|e| eyre!("Unable to write the checksum of '{path}' ({e})"))
+    }
+
+    /// Read the checksum next to the file
+    pub fn read_sidecar(path: &str) -> Result<Option<String>> {
+        let sidecar = Self::sidecar_path(path);
+        if !Path::new(&sidecar).exists() {
+            return Ok(None);
+        }
+        let content = fs::read_to_string(&sidecar)
+            .map_err(|e| eyre!("Unable to read the checksum '{sidecar}' ({e})"))?;
+        Ok(content.split_whitespace().next().map(str::to_lowercase))
+    }
+
+    /// Path of the sidecar file
+    fn sidecar_path(path: &str) -> String {
+        format!("{path}.sha256")
+    }
+}
@@ -0,0 +1,104 @@
+use std::{
+    cmp::Reverse,
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::{
+    config::LocalPolicy,
+    eyre,
+    job::{Job, JobState},
+    Result,
+};
+
+pub struct Cleanup {}
+
+/// Apply the local policy once a file is uploaded and moved
+impl Cleanup {
+    pub fn start(policy: &LocalPolicy, path: &str, dir: &str) -> Result<()> {
+        match policy {
+            LocalPolicy::Keep => Ok(()),
+            LocalPolicy::Delete => Self::delete(path),
+            LocalPolicy::Archive { path: archive } => Self::archive(path, archive),
+            LocalPolicy::KeepRecent { count } => Self::keep_recent(dir, *count),
+        }
+    }
+
+    /// Delete the file and its checksum
+    fn delete(path: &str) -> Result<()> {
+        fs::remove_file(path).map_err(|e| eyre!("Unable to delete '{path}' ({e})"))?;
+        let sidecar = format!("{path}.sha256");
+        if Path::new(&sidecar).exists() {
+            fs::remove_file(&sidecar).map_err(|e| eyre!("Unable to delete '{sidecar}' ({e})"))?;
+        }
+        println!("Deleted '{path}'");
+        Ok(())
+    }
+
+    /// Move the file and its checksum to the archive directory
+    fn archive(path: &str, archive: &str) -> Result<()> {
+        fs::create_dir_all(archive)
+            .map_err(|e| eyre!("Unable to create the archive directory '{archive}' ({e})"))?;
+        let from = fs::canonicalize(path)
+            .map_err(|e| eyre!("Unable to resolve '{path}' ({e})"))?
+            .to_string_lossy()
+            .to_string();
+        let name = Path::new(path)
+            .file_name()
+            .ok_or_else(|| eyre!("Unable to extract file name"))?;
+        let target = Path::new(archive).join(name);
+
+        Self::move_file(Path::new(path), &target)?;
+        let sidecar = PathBuf::from(format!("{path}.sha256"));
+        if sidecar.exists() {
+            Self::move_file(
+                &sidecar,
+                &PathBuf::from(format!("{}.sha256", target.display())),
+            )?;
+        }
+        Job::relocate(&from, &target.to_string_lossy())?;
+
+        println!("Archived '{path}' to '{}'", target.display());
+        Ok(())
+    }
+
+    /// Delete the oldest uploaded files of the local directory
+    fn keep_recent(dir: &str, count: usize) -> Result<()> {
+        let dir = fs::canonicalize(dir).map_err(|e| eyre!("Unable to resolve '{dir}' ({e})"))?;
+
+        // Only the files with a finished job have been uploaded, the others were only downloaded
+        let mut uploaded: Vec<(PathBuf, SystemTime)> = Job::load()?
+            .into_iter()
+            .filter(|j| j.state == JobState::Done)
+            .map(|j| PathBuf::from(j.path))
+            .filter(|p| p.parent() == Some(dir.as_path()) && p.exists())
+            .filter_map(|p| {
+                let modified = fs::metadata(&p).and_then(|m| m.modified()).ok()?;
+                Some((p, modified))
+            })
+            .collect();
+        uploaded.sort_by_key(|(_, modified)| Reverse(*modified));
+
+        for (path, _) in uploaded.iter().skip(count) {
+            Self::delete(&path.to_string_lossy())?;
+        }
+        Ok(())
+    }
+
+    /// Move a file, falling back to a copy across filesystems
+    fn move_file(from: &Path, to: &Path) -> Result<()> {
+        if fs::rename(from, to).is_ok() {
+            return Ok(());
+        }
+        fs::copy(from, to)
+            .and_then(|_| fs::remove_file(from))
+            .map_err(|e| {
+                eyre!(
+                    "Unable to move '{}' to '{}' ({e})",
+                    from.display(),
+                    to.display()
+                )
+            })
+    }
+}
@@ -1,11 +1,155 @@
 use clap::{Args, Parser, Subcommand};
 
+use downtobox::{dedupe::DedupeKeep, Tags};
+
 #[derive(Parser)]
 #[command(author, version, about = "I a just a fancy software", long_about = None)]
 #[command(propagate_version = true)]
 pub struct Cli {
     #[command(subcommand)]
     pub subcmd: CliSubCmd,
+
+    /// Download rate limit in bytes per second (e.g. 512K, 5M or 5MiB)
+    #[arg(long, global = true, env = "DOWNTOBOX_LIMITS_DOWNLOAD")]
+    pub limit_down: Option<String>,
+
+    /// Upload rate limit in bytes per second (e.g. 512K, 5M or 5MiB)
+    #[arg(long, global = true, env = "DOWNTOBOX_LIMITS_UPLOAD")]
+    pub limit_up: Option<String>,
+
+    /// Config file to load instead of the default one
+    #[arg(long, global = true, env = "DOWNTOBOX_CONFIG")]
+    pub config: Option<String>,
+
+    /// Profile of the config file overriding the default settings
+    #[arg(long, short = 'P', global = true, env = "DOWNTOBOX_PROFILE")]
+    pub profile: Option<String>,
+
+    #[command(flatten)]
+    pub overrides: CliOverrides,
+}
+
+/// Settings replacing the ones of the config file and of the profile
+///
+/// Precedence: command line > environment > profile > config file > default values
+#[derive(Args)]
+#[command(next_help_heading = "Config overrides")]
+pub struct CliOverrides {
+    /// Directory of the downloaded files
+    #[arg(long, global = true, env = "DOWNTOBOX_LOCAL_PATH")]
+    pub local_path: Option<String>,
+
+    /// Remote folder of the uploaded files
+    #[arg(long, global = true, env = "DOWNTOBOX_DESTINATION_PATH")]
+    pub destination_path: Option<String>,
+
+    /// Uptobox API key
+    #[arg(long, global = true, env = "DOWNTOBOX_API_KEY", hide_env_values = true)]
+    pub api_key: Option<String>,
+
+    /// Base url of the Uptobox API
+    #[arg(long, global = true, env = "DOWNTOBOX_API_URL")]
+    pub api_url: Option<String>,
+
+    /// Size in MiB of the head and tail compared after an upload
+    #[arg(long, global = true, env = "DOWNTOBOX_VERIFY_SAMPLE")]
+    pub verify_sample: Option<String>,
+
+    /// Write a '.sha256' file next to every transferred file (true or false)
+    #[arg(long, global = true, env = "DOWNTOBOX_SIDECAR")]
+    pub sidecar: Option<String>,
+
+    /// Free space in MiB kept on the local filesystem
+    #[arg(long, global = true, env = "DOWNTOBOX_DISK_MARGIN")]
+    pub disk_margin: Option<String>,
+
+    /// Days before the premium expiry from which a warning is shown
+    #[arg(long, global = true, env = "DOWNTOBOX_PREMIUM_WARNING")]
+    pub premium_warning: Option<String>,
+
+    /// Storage quota of the account in GiB
+    #[arg(long, global = true, env = "DOWNTOBOX_STORAGE_QUOTA")]
+    pub storage_quota: Option<String>,
+
+    /// Maximum length of the generated file names
+    #[arg(long, global = true, env = "DOWNTOBOX_MAX_NAME_LENGTH")]
+    pub max_name_length: Option<String>,
+
+    /// Local policy (keep, delete, archive or keep_recent)
+    #[arg(long, global = true, env = "DOWNTOBOX_LOCAL_POLICY_MODE")]
+    pub local_policy_mode: Option<String>,
+
+    /// Archive directory of the archive local policy
+    #[arg(long, global = true, env = "DOWNTOBOX_LOCAL_POLICY_PATH")]
+    pub local_policy_path: Option<String>,
+
+    /// Number of files kept by the keep_recent local policy
+    #[arg(long, global = true, env = "DOWNTOBOX_LOCAL_POLICY_COUNT")]
+    pub local_policy_count: Option<String>,
+
+    /// Suffix of the titles (none, random, hash or tag)
+    #[arg(long, global = true, env = "DOWNTOBOX_SUFFIX_MODE")]
+    pub suffix_mode: Option<String>,
+
+    /// Suffix of the tag suffix mode
+    #[arg(long, global = true, env = "DOWNTOBOX_SUFFIX_TAG")]
+    pub suffix_tag: Option<String>,
+
+    /// Append the suffix to the file names too (true or false)
+    #[arg(long, global = true, env = "DOWNTOBOX_SUFFIX_FILE_NAME")]
+    pub suffix_file_name: Option<String>,
+
+    /// Storage backend (uptobox or webdav)
+    #[arg(long, global = true, env = "DOWNTOBOX_STORAGE_BACKEND")]
+    pub storage_backend: Option<String>,
+
+    /// Url of the WebDAV share
+    #[arg(long, global = true, env = "DOWNTOBOX_STORAGE_URL")]
+    pub storage_url: Option<String>,
+
+    /// Username of the WebDAV share
+    #[arg(long, global = true, env = "DOWNTOBOX_STORAGE_USERNAME")]
+    pub storage_username: Option<String>,
+
+    /// Password of the WebDAV share
+    #[arg(
+        long,
+        global = true,
+        env = "DOWNTOBOX_STORAGE_PASSWORD",
+        hide_env_values = true
+    )]
+    pub storage_password: Option<String>,
+}
+
+impl CliOverrides {
+    /// Dotted keys of the config file with their value
+    pub fn pairs(&self) -> Vec<(&'static str, String)> {
+        [
+            ("local_path", &self.local_path),
+            ("destination_path", &self.destination_path),
+            ("api_key", &self.api_key),
+            ("api_url", &self.api_url),
+            ("verify_sample", &self.verify_sample),
+            ("sidecar", &self.sidecar),
+            ("disk_margin", &self.disk_margin),
+            ("premium_warning", &self.premium_warning),
+            ("storage_quota", &self.storage_quota),
+            ("max_name_length", &self.max_name_length),
+            ("local_policy.mode", &self.local_policy_mode),
+            ("local_policy.path", &self.local_policy_path),
+            ("local_policy.count", &self.local_policy_count),
+            ("suffix.mode", &self.suffix_mode),
+            ("suffix.tag", &self.suffix_tag),
+            ("suffix.file_name", &self.suffix_file_name),
+            ("storage.backend", &self.storage_backend),
+            ("storage.url", &self.storage_url),
+            ("storage.username", &self.storage_username),
+            ("storage.password", &self.storage_password),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| value.clone().map(|value| (key, value)))
+        .collect()
+    }
 }
 
 #[derive(Subcommand)]
@@ -14,6 +158,22 @@ pub enum CliSubCmd {
     Backup(CliBackupSubCmd),
     #[clap(about = "Upload section")]
     Upload(CliUploadSubCmd),
+    #[clap(about = "Verify the checksum of local files")]
+    Verify(CliVerify),
+    #[clap(about = "Remote files section")]
+    Remote(CliRemoteSubCmd),
+    #[clap(about = "Show the uptobox account status")]
+    Account,
+    #[clap(about = "Find and delete remote duplicates")]
+    Dedupe(CliDedupe),
+    #[clap(about = "Sync section")]
+    Sync(CliSyncSubCmd),
+    #[clap(about = "Retitle section")]
+    Retitle(CliRetitleSubCmd),
+    #[clap(about = "Share the remote folders over WebDAV (read-only)")]
+    ServeWebdav(CliServeWebdav),
+    #[clap(about = "Config section")]
+    Config(CliConfigSubCmd),
 }
 
 #[derive(Args)]
@@ -28,6 +188,18 @@ pub struct CliUploadSubCmd {
     pub subcmd: CliUploadCmd,
 }
 
+#[derive(Args)]
+pub struct CliSyncSubCmd {
+    #[clap(subcommand)]
+    pub subcmd: CliSyncCmd,
+}
+
+#[derive(Args)]
+pub struct CliRetitleSubCmd {
+    #[clap(subcommand)]
+    pub subcmd: CliRetitleCmd,
+}
+
 #[derive(Subcommand)]
 pub enum CliBackupCmd {
     #[clap(about = "Show section")]
@@ -44,14 +216,25 @@ pub enum CliUploadCmd {
     Film(CliUpload),
 }
 
-#[derive(Args, Debug)]
-pub struct CliBackup {
-    /// Title of the show
-    pub title: String,
+#[derive(Subcommand)]
+pub enum CliSyncCmd {
+    #[clap(about = "Show section")]
+    Show(CliSync),
+    #[clap(about = "Film section")]
+    Film(CliSync),
+}
 
-    /// Links of the show
-    pub links: Vec<String>,
+#[derive(Subcommand)]
+pub enum CliRetitleCmd {
+    #[clap(about = "Show section")]
+    Show(CliRetitle),
+    #[clap(about = "Film section")]
+    Film(CliRetitle),
+}
 
+/// Tags of the generated titles
+#[derive(Args, Debug)]
+pub struct CliTags {
     /// Language of the show
     #[arg(long, short, default_value_t = String::new())]
     pub languages: String,
@@ -59,6 +242,53 @@ pub struct CliBackup {
     /// Source of the show
     #[arg(long, short, default_value_t = String::new())]
     pub sources: String,
+
+    /// Streaming service of the show (e.g. AMZN, NF), detected from the file name by default
+    #[arg(long, default_value_t = String::new())]
+    pub services: String,
+
+    /// Edition of the show (e.g. REPACK, EXTENDED), detected from the file name by default
+    #[arg(long, default_value_t = String::new())]
+    pub editions: String,
+
+    /// Release group replacing the one of the file name
+    #[arg(long, conflicts_with = "no_group")]
+    pub group: Option<String>,
+
+    /// Drop the release group of the file name
+    #[arg(long)]
+    pub no_group: bool,
+}
+
+impl From<&CliTags> for Tags {
+    fn from(tags: &CliTags) -> Self {
+        Self {
+            languages: tags.languages.clone(),
+            sources: tags.sources.clone(),
+            services: tags.services.clone(),
+            editions: tags.editions.clone(),
+            group: match tags.no_group {
+                true => Some(String::new()),
+                false => tags.group.clone(),
+            },
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct CliBackup {
+    /// Title of the show
+    pub title: String,
+
+    /// Links of the show
+    pub links: Vec<String>,
+
+    #[command(flatten)]
+    pub tags: CliTags,
+
+    /// Keep the local files regardless of the local policy
+    #[arg(long)]
+    pub keep_local: bool,
 }
 
 #[derive(Args, Debug)]
@@ -69,11 +299,196 @@ pub struct CliUpload {
     /// Paths of the show
     pub paths: Vec<String>,
 
-    /// Language of the show
-    #[arg(long, short, default_value_t = String::new())]
-    pub languages: String,
+    #[command(flatten)]
+    pub tags: CliTags,
+}
 
-    /// Source of the show
-    #[arg(long, short, default_value_t = String::new())]
-    pub sources: String,
+#[derive(Args, Debug)]
+pub struct CliSync {
+    /// Title of the show
+    pub title: String,
+
+    /// Local directory of the show
+    pub dir: String,
+
+    #[command(flatten)]
+    pub tags: CliTags,
+
+    /// Delete the remote files of the title missing from the local directory
+    #[arg(long)]
+    pub delete: bool,
+
+    /// Only print the plan
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Do not ask for a confirmation before applying the plan
+    #[arg(long, short)]
+    pub yes: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct CliRetitle {
+    /// Title of the show
+    pub title: String,
+
+    /// Links of the show
+    pub links: Vec<String>,
+
+    #[command(flatten)]
+    pub tags: CliTags,
+
+    /// Size in MiB of the head downloaded to read the tracks
+    #[arg(long, default_value_t = 4)]
+    pub head: u64,
+
+    /// Only print the new names
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct CliVerify {
+    /// Paths of the files
+    pub paths: Vec<String>,
+}
+
+#[derive(Args)]
+pub struct CliRemoteSubCmd {
+    #[clap(subcommand)]
+    pub subcmd: CliRemoteCmd,
+}
+
+#[derive(Subcommand)]
+pub enum CliRemoteCmd {
+    #[clap(about = "List a remote folder")]
+    Ls(CliRemoteLs),
+    #[clap(about = "Create a remote folder")]
+    Mkdir(CliRemoteMkdir),
+    #[clap(about = "Move a remote file or folder into a folder")]
+    Mv(CliRemoteMv),
+    #[clap(about = "Delete remote files or folders")]
+    Rm(CliRemoteRm),
+    #[clap(about = "Rename a remote file or folder")]
+    Rename(CliRemoteRename),
+}
+
+#[derive(Args, Debug)]
+pub struct CliRemoteLs {
+    /// Path of the folder
+    #[arg(default_value_t = String::from("//"))]
+    pub path: String,
+
+    /// List the sub folders
+    #[arg(long, short = 'R')]
+    pub recursive: bool,
+
+    /// Print the listing as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct CliRemoteMkdir {
+    /// Path of the folder
+    pub path: String,
+
+    /// Create the missing parent folders
+    #[arg(long, short)]
+    pub parents: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct CliRemoteMv {
+    /// Path of the file or folder
+    pub source: String,
+
+    /// Path of the destination folder
+    pub destination: String,
+}
+
+#[derive(Args, Debug)]
+pub struct CliRemoteRm {
+    /// Paths of the files or folders
+    pub paths: Vec<String>,
+
+    /// Delete folders and their content
+    #[arg(long, short)]
+    pub recursive: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct CliRemoteRename {
+    /// Path of the file or folder
+    pub path: String,
+
+    /// New name
+    pub name: String,
+}
+
+#[derive(Args, Debug)]
+pub struct CliDedupe {
+    /// Path of the folder
+    #[arg(default_value_t = String::from("//"))]
+    pub path: String,
+
+    /// Copy to keep in each group of duplicates
+    #[arg(long, short, value_enum, default_value_t = DedupeKeep::Newest)]
+    pub keep: DedupeKeep,
+
+    /// Group the copies of different sizes too
+    #[arg(long)]
+    pub any_size: bool,
+
+    /// Delete the duplicates instead of listing them
+    #[arg(long)]
+    pub delete: bool,
+
+    /// Do not ask for a confirmation before deleting
+    #[arg(long, short)]
+    pub yes: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct CliServeWebdav {
+    /// Remote folder shared as the root
+    #[arg(default_value_t = String::from("//"))]
+    pub path: String,
+
+    /// Address to listen on
+    #[arg(long, short, default_value_t = String::from("127.0.0.1:8080"))]
+    pub address: String,
+
+    /// Redirect the downloads to the remote storage instead of proxying them
+    #[arg(long)]
+    pub redirect: bool,
+}
+
+#[derive(Args)]
+pub struct CliConfigSubCmd {
+    #[clap(subcommand)]
+    pub subcmd: CliConfigCmd,
+}
+
+#[derive(Subcommand)]
+pub enum CliConfigCmd {
+    #[clap(about = "Ask for the settings and write the config file")]
+    Init,
+    #[clap(about = "Validate the config file and report every problem")]
+    Check,
+    #[clap(about = "List the profiles of the config file")]
+    Profiles,
+    #[clap(about = "Store the API key in the keyring or in an encrypted file")]
+    SetKey(CliSetKey),
+}
+
+#[derive(Args, Debug)]
+pub struct CliSetKey {
+    /// Keyring service of the key
+    #[arg(long, default_value_t = String::from("downtobox"))]
+    pub service: String,
+
+    /// Encrypted file storing the key instead of the keyring
+    #[arg(long)]
+    pub file: Option<String>,
 }
@@ -1,4 +1,3 @@
-use rand::{distributions::Alphanumeric, Rng};
 use std::process::Command as Execute;
 
 use crate::{eyre, Result};
@@ -14,18 +13,11 @@ impl Command {
         }
     }
 
-    /// Update the file title
-    pub fn update_title(path: &str, title: &str, hash: bool) -> Result<()> {
-        let hash = match hash {
-            true => format!(
-                " - {}",
-                rand::thread_rng()
-                    .sample_iter(&Alphanumeric)
-                    .take(7)
-                    .map(char::from)
-                    .collect::<String>()
-            ),
-            false => String::new(),
+    /// Update the file title, followed by its suffix
+    pub fn update_title(path: &str, title: &str, suffix: Option<&str>) -> Result<()> {
+        let hash = match suffix {
+            Some(suffix) => format!(" - {suffix}"),
+            None => String::new(),
         };
 
         let cmd = Execute::new("mkvpropedit")
@@ -1,46 +1,340 @@
 use directories::{ProjectDirs, UserDirs};
 use serde::{de::Error, Deserialize, Serialize};
 use std::{
+    collections::BTreeMap,
     fs::{self, File},
     io::Write,
-    path::PathBuf,
+    path::Path,
 };
+use toml::{Table, Value};
 
-use crate::{eyre, Result};
+use crate::{eyre, secret::Secret, Result};
+
+/// API key of the default config file
+pub const DEFAULT_API_KEY: &str = "uptobox_api_key";
 
 #[derive(Deserialize, Serialize, Debug)]
 pub struct Config {
     #[serde(deserialize_with = "deserialize_local_path")]
     pub local_path: String,
     pub destination_path: String,
+    /// Uptobox API key, or a reference such as `keyring:downtobox` or `file:/run/secrets/uptobox`
     pub api_key: String,
+    /// Base url of the Uptobox API
+    #[serde(default = "default_api_url")]
+    pub api_url: String,
+    /// Size in MiB of the head and tail compared after an upload (0 to disable)
+    #[serde(default)]
+    pub verify_sample: u64,
+    /// Write a '.sha256' file next to every transferred file
+    #[serde(default)]
+    pub sidecar: bool,
+    /// Free space in MiB kept on the local filesystem after a download
+    #[serde(default = "default_disk_margin")]
+    pub disk_margin: u64,
+    /// Days before the premium expiry from which a warning is shown
+    #[serde(default = "default_premium_warning")]
+    pub premium_warning: i64,
+    /// Storage quota of the account in GiB (0 when unknown)
+    #[serde(default)]
+    pub storage_quota: u64,
+    /// Maximum length of the generated file names, the title is truncated first (0 for no limit)
+    #[serde(default = "default_max_name_length")]
+    pub max_name_length: usize,
+    /// Bandwidth limits of the transfers
+    #[serde(default)]
+    pub limits: Limits,
+    /// What to do with the local file once it is uploaded
+    #[serde(default)]
+    pub local_policy: LocalPolicy,
+    /// Hoster the files are backed up to
+    #[serde(default)]
+    pub storage: Storage,
+    /// Aliases of the sources and languages added to the built-in ones
+    #[serde(default)]
+    pub aliases: Aliases,
+    /// Suffix appended to the title of the uploaded files
+    #[serde(default)]
+    pub suffix: Suffix,
+}
+
+/// Rates in bytes per second with an optional K, M or G binary suffix (empty when unlimited)
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
+pub struct Limits {
+    #[serde(default)]
+    pub download: String,
+    #[serde(default)]
+    pub upload: String,
+    /// Rates replacing the default ones during a time window
+    #[serde(default)]
+    pub schedule: Vec<LimitSchedule>,
+}
+
+/// Time window (HH:MM) with its own rates
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct LimitSchedule {
+    pub start: String,
+    pub end: String,
+    #[serde(default)]
+    pub download: String,
+    #[serde(default)]
+    pub upload: String,
+}
+
+/// Display strings of the title tags with the command line tokens matching them
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
+pub struct Aliases {
+    #[serde(default)]
+    pub sources: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    pub languages: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    pub services: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    pub editions: BTreeMap<String, Vec<String>>,
+}
+
+/// Suffix appended to the segment title, and to the file name with `file_name`
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
+pub struct Suffix {
+    #[serde(default)]
+    pub mode: SuffixMode,
+    /// Suffix of the `tag` mode
+    #[serde(default)]
+    pub tag: String,
+    #[serde(default)]
+    pub file_name: bool,
+}
+
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SuffixMode {
+    /// No suffix
+    None,
+    /// 7 random alphanumerics
+    #[default]
+    Random,
+    /// First 7 characters of the BLAKE3 hash of the file
+    Hash,
+    /// Tag of the config
+    Tag,
+}
+
+/// Policy applied to the local files after a successful backup
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum LocalPolicy {
+    /// Keep every file
+    #[default]
+    Keep,
+    /// Delete the file
+    Delete,
+    /// Move the file to an archive directory
+    Archive {
+        #[serde(deserialize_with = "deserialize_local_path")]
+        path: String,
+    },
+    /// Keep only the most recent uploaded files
+    KeepRecent { count: usize },
+}
+
+/// Hoster the files are backed up to
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
+#[serde(tag = "backend", rename_all = "snake_case")]
+pub enum Storage {
+    /// Uptobox account of `api_key`
+    #[default]
+    Uptobox,
+    /// WebDAV share, links are remote paths such as '//Shows/file.mkv'
+    Webdav {
+        url: String,
+        #[serde(default)]
+        username: String,
+        #[serde(default)]
+        password: String,
+    },
 }
 
 impl Config {
-    pub fn init() -> Result<Config> {
-        let dir = ProjectDirs::from("dev", "latruiterouge", "downtobox")
-            .ok_or_else(|| eyre!("The configuration path cannot be determined"))?;
-        let toml = format!("{}/config.toml", dir.config_dir().display());
-
-        // Create a config if it does not exist
-        if !dir.config_dir().exists() {
-            fs::create_dir_all(dir.config_dir())
-                .map_err(|e| eyre!("Unable to create the config file ({e})"))?;
-            let mut file =
-                File::create(&toml).map_err(|e| eyre!("Unable to create the config file ({e})"))?;
+    /// Load the config file, a profile and the overrides of the environment and the command line
+    pub fn init(
+        path: Option<&str>,
+        profile: Option<&str>,
+        overrides: &[(&str, String)],
+    ) -> Result<Config> {
+        Self::load(Self::read(path)?, profile, overrides)
+    }
+
+    /// Read a config file, the default one is created if it does not exist
+    pub fn read(path: Option<&str>) -> Result<Table> {
+        let toml = Self::path(path)?;
+        if path.is_none() && !Path::new(&toml).exists() {
             let toml_config = toml::to_string(&Self::default())
                 .map_err(|e| eyre!("Unable to create the config file ({e})"))?;
-            file.write_all(toml_config.as_bytes())
-                .map_err(|e| eyre!("Unable to create the config file ({e})"))?;
+            Self::write(&toml, &toml_config)?;
+        }
+
+        let config = fs::read_to_string(&toml)
+            .map_err(|e| eyre!("Unable to open the config file '{toml}' ({e})"))?;
+        toml::from_str(&config).map_err(|e| eyre!("Unable to parse the config file ({e})"))
+    }
+
+    /// Path of a config file, the default one when none is given
+    pub fn path(path: Option<&str>) -> Result<String> {
+        match path {
+            Some(path) => Ok(path.to_string()),
+            None => {
+                let dir = ProjectDirs::from("dev", "latruiterouge", "downtobox")
+                    .ok_or_else(|| eyre!("The configuration path cannot be determined"))?;
+                Ok(format!("{}/config.toml", dir.config_dir().display()))
+            }
+        }
+    }
+
+    /// Write a config file and its directory
+    pub fn write(path: &str, toml: &str) -> Result<()> {
+        if let Some(dir) = Path::new(path).parent() {
+            fs::create_dir_all(dir)
+                .map_err(|e| eyre!("Unable to create the config file '{path}' ({e})"))?;
+        }
+        let mut file = File::create(path)
+            .map_err(|e| eyre!("Unable to create the config file '{path}' ({e})"))?;
+        file.write_all(toml.as_bytes())
+            .map_err(|e| eyre!("Unable to write the config file '{path}' ({e})"))
+    }
+
+    /// Apply a profile and the overrides over the default settings
+    ///
+    /// Precedence: overrides > profile > config file > default values. The settings of a
+    /// profile are merged key by key into the default ones, the overrides are dotted keys
+    /// such as `storage.url`.
+    pub fn load(
+        table: Table,
+        profile: Option<&str>,
+        overrides: &[(&str, String)],
+    ) -> Result<Config> {
+        Self::load_unresolved(table, profile, overrides)?.resolve()
+    }
+
+    /// Apply a profile and the overrides, keeping the secret references as written
+    pub fn load_unresolved(
+        mut table: Table,
+        profile: Option<&str>,
+        overrides: &[(&str, String)],
+    ) -> Result<Config> {
+        let profiles = match table.remove("profiles") {
+            Some(Value::Table(profiles)) => profiles,
+            Some(_) => {
+                return Err(eyre!(
+                    "Unable to parse the config file ('profiles' is not a table)"
+                ))
+            }
+            None => Table::new(),
+        };
+
+        if let Some(name) = profile {
+            let overrides = match profiles.get(name) {
+                Some(Value::Table(overrides)) => overrides,
+                Some(_) => {
+                    return Err(eyre!(
+                        "Unable to parse the config file ('profiles.{name}' is not a table)"
+                    ))
+                }
+                None => {
+                    return Err(eyre!(
+                        "The profile '{name}' does not exist (available: {})",
+                        profiles.keys().cloned().collect::<Vec<_>>().join(", ")
+                    ))
+                }
+            };
+            Self::merge(&mut table, overrides.clone());
+        }
+
+        for (key, value) in overrides {
+            Self::set(&mut table, key, value)?;
+        }
+
+        Value::Table(table)
+            .try_into()
+            .map_err(|e| eyre!("Unable to parse the config file ({e})"))
+    }
+
+    /// Resolve the references to the keyring or to a file of the secrets used by the storage
+    pub fn resolve(mut self) -> Result<Config> {
+        match &mut self.storage {
+            Storage::Uptobox => self.api_key = Secret::resolve(&self.api_key)?,
+            Storage::Webdav { password, .. } => *password = Secret::resolve(password)?,
+        }
+        Ok(self)
+    }
+
+    /// Merge the settings of a table into another, the sections are merged key by key
+    fn merge(table: &mut Table, overrides: Table) {
+        for (key, value) in overrides {
+            match (table.get_mut(&key), value) {
+                (Some(Value::Table(section)), Value::Table(overrides)) => {
+                    Self::merge(section, overrides)
+                }
+                (_, value) => {
+                    table.insert(key, value);
+                }
+            }
+        }
+    }
+
+    /// Set a setting from its dotted key
+    fn set(table: &mut Table, key: &str, value: &str) -> Result<()> {
+        let value = match key {
+            "verify_sample" | "disk_margin" | "premium_warning" | "storage_quota"
+            | "max_name_length" | "local_policy.count" => Value::Integer(
+                value
+                    .parse()
+                    .map_err(|e| eyre!("Unable to parse '{key}' from '{value}' ({e})"))?,
+            ),
+            "sidecar" | "suffix.file_name" => Value::Boolean(
+                value
+                    .parse()
+                    .map_err(|e| eyre!("Unable to parse '{key}' from '{value}' ({e})"))?,
+            ),
+            _ => Value::String(value.to_string()),
+        };
+
+        let (sections, name) = match key.rsplit_once('.') {
+            Some((sections, name)) => (sections.split('.').collect(), name),
+            None => (vec![], key),
+        };
+        let mut table = table;
+        for section in sections {
+            table = match table
+                .entry(section)
+                .or_insert_with(|| Value::Table(Table::new()))
+            {
+                Value::Table(table) => table,
+                _ => return Err(eyre!("Unable to set '{key}' ('{section}' is not a table)")),
+            };
         }
+        table.insert(name.to_string(), value);
+        Ok(())
+    }
 
-        // Load config
-        let config =
-            fs::read_to_string(&toml).map_err(|e| eyre!("Unable to open the config file ({e})"))?;
-        let config: Config =
-            toml::from_str(&config).map_err(|e| eyre!("Unable to parse the config file ({e})"))?;
+    /// Names of the profiles of a config file
+    /// Expand a leading '~/' to the home directory
+    pub fn expand_home(path: &str) -> Result<String> {
+        match path.strip_prefix("~/") {
+            Some(path) => {
+                let user = UserDirs::new()
+                    .ok_or_else(|| eyre!("Unable to find the home directory to expand '~/'"))?;
+                Ok(user.home_dir().join(path).to_string_lossy().to_string())
+            }
+            None => Ok(path.to_string()),
+        }
+    }
 
-        Ok(config)
+    pub fn profiles(table: &Table) -> Vec<String> {
+        match table.get("profiles") {
+            Some(Value::Table(profiles)) => profiles.keys().cloned().collect(),
+            _ => vec![],
+        }
     }
 }
 
@@ -49,29 +343,44 @@ impl Default for Config {
         Self {
             local_path: String::from("~/Downloads"),
             destination_path: String::from("//"),
-            api_key: String::from("uptobox_api_key"),
+            api_key: String::from(DEFAULT_API_KEY),
+            api_url: default_api_url(),
+            verify_sample: 0,
+            sidecar: false,
+            disk_margin: default_disk_margin(),
+            premium_warning: default_premium_warning(),
+            storage_quota: 0,
+            max_name_length: default_max_name_length(),
+            limits: Limits::default(),
+            local_policy: LocalPolicy::Keep,
+            storage: Storage::Uptobox,
+            aliases: Aliases::default(),
+            suffix: Suffix::default(),
         }
     }
 }
 
+fn default_api_url() -> String {
+    String::from("https://uptobox.com/api")
+}
+
+fn default_disk_margin() -> u64 {
+    512
+}
+
+fn default_premium_warning() -> i64 {
+    7
+}
+
+fn default_max_name_length() -> usize {
+    250
+}
+
 fn deserialize_local_path<'de, D>(deserializer: D) -> Result<String, D::Error>
 where
     D: serde::Deserializer<'de>,
 {
     let value = String::deserialize(deserializer)?;
-    if value.starts_with("~/") {
-        match UserDirs::new() {
-            Some(user) => {
-                let user = user.home_dir();
-                let path = PathBuf::from(value.trim_start_matches("~/"));
-                let path = user.join(path);
-                Ok(path.to_string_lossy().to_string())
-            }
-            None => Err(Error::custom(
-                "Unable to parse 'local_path' from the config",
-            )),
-        }
-    } else {
-        Ok(value)
-    }
+    Config::expand_home(&value)
+        .map_err(|_| Error::custom("Unable to parse 'local_path' from the config"))
 }
@@ -0,0 +1,253 @@
+use indicatif::HumanBytes;
+use itertools::Itertools;
+use std::cmp::Reverse;
+
+use crate::{
+    config::Suffix,
+    remote::Remote,
+    storage::{RemoteFile, RemoteStorage},
+    title::Title,
+    Result,
+};
+
+pub struct Dedupe {}
+
+/// Copy of a file to keep in a group of duplicates
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+pub enum DedupeKeep {
+    Newest,
+    Largest,
+}
+
+/// Remote duplicates
+impl Dedupe {
+    pub async fn start(
+        storage: &dyn RemoteStorage,
+        path: &str,
+        suffix: &Suffix,
+        keep: DedupeKeep,
+        any_size: bool,
+        (delete, yes): (bool, bool),
+    ) -> Result<()> {
+        let files = Self::walk(storage, path).await?;
+        let groups = Self::groups(files, suffix, keep, any_size);
+
+        if groups.is_empty() {
+            println!("No duplicates found in '{path}'");
+            return Ok(());
+        }
+
+        // Report the duplicates, the first file of each group is kept
+        for group in &groups {
+            println!("\n{} ({} copies)", group[0].1.file_name, group.len());
+            for (i, (path, file)) in group.iter().enumerate() {
+                println!(
+                    "  {:<6}  {:>10}  {:<19}  {}",
+                    if i == 0 { "keep" } else { "delete" },
+                    HumanBytes(file.file_size).to_string(),
+                    file.file_created,
+                    path
+                );
+            }
+        }
+
+        let duplicates: Vec<&str> = groups
+            .iter()
+            .flat_map(|g| g.iter().skip(1).map(|(_, f)| f.file_code.as_str()))
+            .collect();
+        if !delete {
+            println!(
+                "\nDry run, {} file(s) would be deleted (use --delete)",
+                duplicates.len()
+            );
+            return Ok(());
+        }
+        let question = format!("Delete {} file(s)?", duplicates.len());
+        if !yes && !Remote::confirm(&question)? {
+            println!("Aborted");
+            return Ok(());
+        }
+
+        storage.delete_files(&duplicates).await?;
+        println!("Deleted {} file(s)", duplicates.len());
+        Ok(())
+    }
+
+    /// Group the files by name and size, the copy to keep comes first in each group
+    fn groups(
+        files: Vec<(String, RemoteFile)>,
+        suffix: &Suffix,
+        keep: DedupeKeep,
+        any_size: bool,
+    ) -> Vec<Vec<(String, RemoteFile)>> {
+        files
+            .into_iter()
+            .map(|(path, file)| {
+                let key = Self::normalize(suffix, &file.file_name);
+                let size = if any_size { 0 } else { file.file_size };
+                ((key, size), (path, file))
+            })
+            .into_group_map()
+            .into_iter()
+            .filter(|(_, files)| files.len() > 1)
+            .sorted_by(|a, b| a.0.cmp(&b.0))
+            .map(|(_, mut files)| {
+                match keep {
+                    DedupeKeep::Newest => {
+                        files.sort_by(|a, b| b.1.file_created.cmp(&a.1.file_created))
+                    }
+                    DedupeKeep::Largest => files.sort_by_key(|(_, f)| Reverse(f.file_size)),
+                }
+                files
+            })
+            .collect()
+    }
+
+    /// List every file under a folder with its path
+    async fn walk(storage: &dyn RemoteStorage, path: &str) -> Result<Vec<(String, RemoteFile)>> {
+        let mut files = vec![];
+        let mut folders = vec![Remote::normalize(path)];
+        while let Some(folder) = folders.pop() {
+            let listing = storage.list(&folder).await?;
+            folders.extend(
+                listing
+                    .folders
+                    .iter()
+                    .map(|f| Remote::join(&folder, &Remote::folder_name(f))),
+            );
+            files.extend(
+                listing
+                    .files
+                    .into_iter()
+                    .map(|f| (Remote::join(&folder, &f.file_name), f)),
+            );
+        }
+        Ok(files)
+    }
+
+    /// Normalise a file name without the suffix of the file names, its case and its separators
+    ///
+    /// The tags are kept, the releases of an episode in other languages or resolutions differ.
+    fn normalize(suffix: &Suffix, name: &str) -> String {
+        let name = Title::strip_suffix(suffix, name).unwrap_or(name.trim_end_matches(".mkv"));
+        name.to_lowercase()
+            .split(['.', ' ', '_', '-'])
+            .filter(|s| !s.is_empty())
+            .join(" ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SuffixMode;
+
+    fn file(name: &str, size: u64, created: &str) -> (String, RemoteFile) {
+        (
+            format!("//Shows/{name}"),
+            RemoteFile {
+                file_code: format!("{name}-{size}-{created}"),
+                file_name: name.to_string(),
+                file_size: size,
+                file_created: created.to_string(),
+            },
+        )
+    }
+
+    fn random() -> Suffix {
+        Suffix {
+            mode: SuffixMode::Random,
+            file_name: true,
+            ..Suffix::default()
+        }
+    }
+
+    #[test]
+    fn normalize_case_and_separators() {
+        assert_eq!(
+            Dedupe::normalize(&Suffix::default(), "My_Show s01e02-GRP.mkv"),
+            "my show s01e02 grp"
+        );
+    }
+
+    #[test]
+    fn normalize_keeps_the_tags() {
+        assert_ne!(
+            Dedupe::normalize(
+                &Suffix::default(),
+                "My.Show.S01E02.VFF.1080p.WEBDL.EAC3.h264.mkv"
+            ),
+            Dedupe::normalize(
+                &Suffix::default(),
+                "My.Show.S01E02.VOSTFR.2160p.WEBDL.EAC3.h264.mkv"
+            )
+        );
+        assert_ne!(
+            Dedupe::normalize(&Suffix::default(), "My.Show.S01E02.1080p.mkv"),
+            Dedupe::normalize(&Suffix::default(), "My.Show.S01E03.1080p.mkv")
+        );
+    }
+
+    #[test]
+    fn normalize_strips_the_file_name_suffix() {
+        assert_eq!(
+            Dedupe::normalize(&random(), "My.Show.S01E02.1080p.h264.aB3dE5f.mkv"),
+            Dedupe::normalize(&random(), "My.Show.S01E02.1080p.h264.xY9zW8v.mkv")
+        );
+        assert_eq!(
+            Dedupe::normalize(&random(), "My.Show.S01E02.1080p.h264.aB3dE5f.mkv"),
+            "my show s01e02 1080p h264"
+        );
+
+        // Only where the uploads write it, and when the file names have one
+        assert_ne!(
+            Dedupe::normalize(&random(), "My.Film.2023.1080p.UNRATED.mkv"),
+            Dedupe::normalize(&random(), "My.Film.2023.1080p.HDLight.mkv")
+        );
+        assert_ne!(
+            Dedupe::normalize(&Suffix::default(), "My.Show.S01E02.h264.aB3dE5f.mkv"),
+            Dedupe::normalize(&Suffix::default(), "My.Show.S01E02.h264.xY9zW8v.mkv")
+        );
+    }
+
+    #[test]
+    fn groups_by_name_and_size() {
+        let files = vec![
+            file("My.Show.S01E02.1080p.h264.mkv", 100, "2024-01-01 00:00:00"),
+            file(
+                "My.Show.S01E02.1080p.h264.aB3dE5f.mkv",
+                100,
+                "2024-03-01 00:00:00",
+            ),
+            file(
+                "My.Show.S01E02.1080p.h264.xY9zW8v.mkv",
+                200,
+                "2024-02-01 00:00:00",
+            ),
+            file("My.Show.S01E02.2160p.h264.mkv", 100, "2024-01-01 00:00:00"),
+        ];
+
+        let groups = Dedupe::groups(files.clone(), &random(), DedupeKeep::Newest, false);
+        assert_eq!(groups.len(), 1);
+        let names: Vec<&str> = groups[0]
+            .iter()
+            .map(|(_, f)| f.file_name.as_str())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "My.Show.S01E02.1080p.h264.aB3dE5f.mkv",
+                "My.Show.S01E02.1080p.h264.mkv"
+            ]
+        );
+
+        // Any size, the largest copy is kept
+        let groups = Dedupe::groups(files, &random(), DedupeKeep::Largest, true);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].len(), 3);
+        assert_eq!(
+            groups[0][0].1.file_name,
+            "My.Show.S01E02.1080p.h264.xY9zW8v.mkv"
+        );
+    }
+}
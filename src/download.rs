@@ -1,17 +1,27 @@
 use futures_util::StreamExt;
-use indicatif::{ProgressBar, ProgressState, ProgressStyle};
-use reqwest::Client;
-use std::{cmp::min, fs::File, io::Write};
+use reqwest::{header::RANGE, Client, StatusCode};
+use sha2::{Digest, Sha256};
+use std::{
+    cmp::min,
+    fs::{self, File},
+    io::Write,
+};
 
-use crate::{eyre, Result};
+use crate::{eyre, interrupt::Interrupt, limit::Limiter, storage::RemoteStorage, Result};
 
 pub struct Download {}
 
 /// Download the file from uptobox
 impl Download {
-    pub async fn start(client: &Client, url: &str, path: &str) -> Result<()> {
-        let res = client
-            .get(url)
+    pub async fn start(
+        (client, storage): (&Client, &dyn RemoteStorage),
+        limiter: &Limiter,
+        url: &str,
+        path: &str,
+        progress: impl Fn(u64, u64),
+    ) -> Result<String> {
+        let res = storage
+            .download(client, url)
             .send()
             .await
             .map_err(|e| eyre!("Unable to download '{url}' ({e})"))?;
@@ -20,35 +30,90 @@ impl Download {
             .content_length()
             .ok_or_else(|| eyre!("Failed to get content length from '{}'", &url))?;
 
-        let pb = Self::set_progress_bar(size, url)?;
+        progress(0, size);
 
+        // Download into a temporary file until it is complete
+        let part = format!("{path}.part");
         let mut file =
-            File::create(path).map_err(|e| eyre!("Failed to create the file '{path}' ({e})"))?;
+            File::create(&part).map_err(|e| eyre!("Failed to create the file '{part}' ({e})"))?;
         let mut downloaded: u64 = 0;
+        let mut hasher = Sha256::new();
         let mut stream = res.bytes_stream();
 
         while let Some(bytes) = stream.next().await {
+            // Roll back the download when interrupted
+            if Interrupt::is_set() {
+                drop(file);
+                fs::remove_file(&part)
+                    .map_err(|e| eyre!("Failed to remove the file '{part}' ({e})"))?;
+                return Err(eyre!("Download of '{url}' interrupted"));
+            }
+
             let chunk = bytes.map_err(|e| eyre!("Error while downloading '{url}' ({e})"))?;
+            limiter.acquire(chunk.len()).await;
             Write::write_all(&mut file, &chunk)
                 .map_err(|e| eyre!("Error while savin '{url}' ({e})"))?;
+            hasher.update(&chunk);
             let new = min(downloaded + (chunk.len() as u64), size);
             downloaded = new;
-            pb.set_position(new);
+            progress(new, size);
+        }
+
+        if downloaded != size {
+            return Err(eyre!(
+                "Incomplete download of '{url}' ({downloaded} of {size} bytes)"
+            ));
         }
 
-        pb.finish_with_message(format!("Downloaded {} to {}", url, path));
+        file.sync_all()
+            .map_err(|e| eyre!("Error while savin '{url}' ({e})"))?;
+        fs::rename(&part, path)
+            .map_err(|e| eyre!("Failed to rename '{part}' to '{path}' ({e})"))?;
 
-        Ok(())
+        Ok(format!("{:x}", hasher.finalize()))
     }
 
-    /// Set the progress bar
-    fn set_progress_bar(size: u64, url: &str) -> Result<ProgressBar> {
-        let pb = ProgressBar::new(size);
-        pb.set_style(ProgressStyle::with_template("{msg}\n {spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({eta}) {bytes_per_sec}")
-            .map_err(|e| eyre!("The progress bar cannot be initialized ({e})"))?
-            .with_key("eta", |state: &ProgressState, w: &mut dyn std::fmt::Write| write!(w, "{:.1}s", state.eta().as_secs_f64()).unwrap())
-            .progress_chars("#>-"));
-        pb.set_message(format!("Downloading {}", url));
-        Ok(pb)
+    /// Download the first bytes of the file, the caller removes it when this fails
+    pub async fn head(
+        (client, storage): (&Client, &dyn RemoteStorage),
+        url: &str,
+        path: &str,
+        size: u64,
+    ) -> Result<()> {
+        let res = storage
+            .download(client, url)
+            .header(RANGE, format!("bytes=0-{}", size.saturating_sub(1)))
+            .send()
+            .await
+            .map_err(|e| eyre!("Unable to download '{url}' ({e})"))?;
+        if !matches!(res.status(), StatusCode::PARTIAL_CONTENT | StatusCode::OK) {
+            return Err(eyre!(
+                "Unable to download the head of '{url}' (status: {})",
+                res.status()
+            ));
+        }
+
+        let mut file =
+            File::create(path).map_err(|e| eyre!("Failed to create the file '{path}' ({e})"))?;
+        let mut downloaded: u64 = 0;
+        let mut stream = res.bytes_stream();
+
+        // The whole file is sent when the range is not supported
+        while let Some(bytes) = stream.next().await {
+            if Interrupt::is_set() {
+                return Err(eyre!("Download of '{url}' interrupted"));
+            }
+
+            let chunk = bytes.map_err(|e| eyre!("Error while downloading '{url}' ({e})"))?;
+            let chunk = &chunk[..min(chunk.len() as u64, size - downloaded) as usize];
+            Write::write_all(&mut file, chunk)
+                .map_err(|e| eyre!("Error while savin '{url}' ({e})"))?;
+            downloaded += chunk.len() as u64;
+            if downloaded >= size {
+                break;
+            }
+        }
+
+        Ok(())
     }
 }
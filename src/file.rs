@@ -1,65 +1,89 @@
-use regex::Regex;
-use rs_uptobox::{GetDownloadUrl, GetDownloadUrlResponse, Uptobox};
-use std::{fs::DirBuilder, path::Path};
+use std::{
+    fs::{self, DirBuilder},
+    path::Path,
+};
 
-use crate::{eyre, Result};
+use crate::{eyre, job::Job, storage::RemoteStorage, Result};
 
 pub struct File {
+    pub code: String,
     pub name: String,
     pub url: String,
     pub dir: String,
     pub path: String,
+    pub size: u64,
 }
 
 /// File informations
 impl File {
-    pub async fn new(uptobox: &Uptobox, url: &str, dir: &str) -> Result<Self> {
-        let file_code = Self::parse_file_code(url)?;
+    pub async fn new(storage: &dyn RemoteStorage, url: &str, dir: &str) -> Result<Self> {
+        let file_code = storage.file_code(url)?;
 
-        // Get the file name
-        let name = uptobox
-            .get_files_informations(vec![&file_code])
+        // Get the file name and size
+        let infos = storage
+            .get_file(&file_code)
             .await
             .map_err(|e| eyre!("Unable to retreive informations for '{url}' ({e})"))?;
-        let name = &name
-            .get(0)
-            .ok_or_else(|| eyre!("Unable to retreive informations for '{url}'"))?
-            .file_name;
+        let (name, size) = (&infos.file_name, infos.file_size);
 
         // Get the file url
-        let url = uptobox
-            .get_download_url(GetDownloadUrl::new(&file_code))
+        let url = storage
+            .get_download_url(&file_code)
             .await
             .map_err(|e| eyre!("Unable to fetch the download link for '{url}' ({e})"))?;
-        let url = match url {
-            GetDownloadUrlResponse::Link(url) => url.dl_link,
-            GetDownloadUrlResponse::Wait(_) => {
-                return Err(eyre!("A premium account is needed to use this software"));
-            }
-        };
 
         let dir = Self::check_output_dir(dir)?;
 
         Ok(Self {
+            code: file_code.clone(),
             name: name.clone(),
             url,
             dir: dir.clone(),
             path: format!("{}/{}", dir, name),
+            size,
         })
     }
 
-    /// Parse the file code from the url
-    fn parse_file_code(url: &str) -> Result<String> {
-        let regex = Regex::new(
-            r#"https://(?:uptobox|uptostream).[a-zA-Z]+/(?P<file_code>[a-zA-Z0-9]{12})"#,
-        )?;
-        Ok(regex
-            .captures(url)
-            .ok_or_else(|| eyre!("Unable to parse the file code for '{url}' (regex: https://(?:uptobox|uptostream).[a-zA-Z]+/(?P<file_code>[a-zA-Z0-9]{{12}}) )"))?
-            .name("file_code")
-            .ok_or_else(|| eyre!("Unable to parse the file code for '{url}' (regex: https://(?:uptobox|uptostream).[a-zA-Z]+/(?P<file_code>[a-zA-Z0-9]{{12}}) )"))?
-            .as_str()
-            .to_string())
+    /// Get the size of the file without resolving its download link
+    pub async fn size(storage: &dyn RemoteStorage, url: &str) -> Result<u64> {
+        let file_code = storage.file_code(url)?;
+        let infos = storage
+            .get_file(&file_code)
+            .await
+            .map_err(|e| eyre!("Unable to retreive informations for '{url}' ({e})"))?;
+        Ok(infos.file_size)
+    }
+
+    /// Check if the file is a matroska
+    pub fn check_name(name: &str) -> Result<()> {
+        if !name.ends_with(".mkv") {
+            Err(eyre!(
+                "This software is only compatible with matroska (.mkv) files"
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Check if the local file is a complete download
+    pub fn check_local(path: &str) -> Result<()> {
+        if path.ends_with(".part") {
+            return Err(eyre!("'{path}' is an incomplete download"));
+        }
+
+        if let Some(job) = Job::find(path)? {
+            let size = fs::metadata(path)
+                .map_err(|e| eyre!("Unable to read the size of '{path}' ({e})"))?
+                .len();
+            if size != job.size {
+                return Err(eyre!(
+                    "'{path}' does not match its recorded size ({size} of {} bytes)",
+                    job.size
+                ));
+            }
+        }
+
+        Ok(())
     }
 
     /// Check if the output directory exists
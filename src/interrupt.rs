@@ -0,0 +1,78 @@
+use std::{
+    process,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+
+use crate::{eyre, Report, Result};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+static RUNNING: AtomicUsize = AtomicUsize::new(0);
+
+pub struct Interrupt {}
+
+/// Ctrl-C handling
+impl Interrupt {
+    /// Install the handler, the first Ctrl-C stops after the current stage and the second aborts
+    ///
+    /// Outside of the stages, such as a prompt or the WebDAV server, the first Ctrl-C aborts.
+    pub fn install() {
+        tokio::spawn(async {
+            while tokio::signal::ctrl_c().await.is_ok() {
+                if INTERRUPTED.swap(true, Ordering::SeqCst) || RUNNING.load(Ordering::SeqCst) == 0 {
+                    eprintln!("\nAborted");
+                    process::exit(130);
+                }
+                eprintln!(
+                    "\nInterrupted, finishing the current stage (press Ctrl-C again to abort)"
+                );
+            }
+        });
+    }
+
+    /// Mark a stage as running until the returned guard is dropped
+    pub fn stage() -> Stage {
+        RUNNING.fetch_add(1, Ordering::SeqCst);
+        Stage {}
+    }
+
+    /// Check if the program has been interrupted
+    pub fn is_set() -> bool {
+        INTERRUPTED.load(Ordering::SeqCst)
+    }
+
+    /// Stop between two stages if the program has been interrupted
+    pub fn check(stage: &str) -> Result<()> {
+        match Self::is_set() {
+            true => Err(eyre!("Interrupted before {stage}")),
+            false => Ok(()),
+        }
+    }
+
+    /// Stop before the next item, printing what was left pending
+    pub fn stop(pending: &[String]) -> Result<()> {
+        match Self::is_set() {
+            true => Err(Self::pending(eyre!("Interrupted by the user"), pending)),
+            false => Ok(()),
+        }
+    }
+
+    /// Print what was left pending if the error comes from an interruption
+    pub fn pending(error: Report, pending: &[String]) -> Report {
+        if Self::is_set() {
+            eprintln!("\nLeft pending:");
+            for item in pending {
+                eprintln!("  {}", item.trim());
+            }
+        }
+        error
+    }
+}
+
+/// Running stage, the first Ctrl-C waits for its end
+pub struct Stage {}
+
+impl Drop for Stage {
+    fn drop(&mut self) {
+        RUNNING.fetch_sub(1, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,125 @@
+use chrono::Local;
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use std::{fs, path::PathBuf};
+
+use crate::{eyre, Result};
+
+/// Record of a transferred file
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Job {
+    pub path: String,
+    #[serde(default)]
+    pub title: String,
+    pub file_code: String,
+    pub size: u64,
+    pub sha256: String,
+    #[serde(default)]
+    pub downloaded_sha256: Option<String>,
+    #[serde(default)]
+    pub state: JobState,
+    pub date: String,
+}
+
+/// Last stage completed by a job
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    /// Downloaded but not uploaded yet
+    Downloaded,
+    /// Uploaded and moved to the destination
+    #[default]
+    Done,
+}
+
+impl Job {
+    pub fn new(
+        path: &str,
+        title: &str,
+        file_code: &str,
+        sha256: &str,
+        downloaded_sha256: Option<String>,
+    ) -> Result<Self> {
+        let size = fs::metadata(path)
+            .map_err(|e| eyre!("Unable to read the size of '{path}' ({e})"))?
+            .len();
+
+        Ok(Self {
+            path: Self::key(path),
+            title: title.to_string(),
+            file_code: file_code.to_string(),
+            size,
+            sha256: sha256.to_string(),
+            downloaded_sha256,
+            state: JobState::Done,
+            date: Local::now().to_rfc3339(),
+        })
+    }
+
+    /// Record of a completed download, before its upload
+    pub fn download(path: &str, file_code: &str, sha256: &str) -> Result<Self> {
+        Ok(Self {
+            state: JobState::Downloaded,
+            ..Self::new(path, "", file_code, sha256, Some(sha256.to_string()))?
+        })
+    }
+
+    /// Load the job records
+    pub fn load() -> Result<Vec<Job>> {
+        let records = Self::records_path()?;
+        if !records.exists() {
+            return Ok(vec![]);
+        }
+        let jobs = fs::read_to_string(&records)
+            .map_err(|e| eyre!("Unable to open the job records ({e})"))?;
+        serde_json::from_str(&jobs).map_err(|e| eyre!("Unable to parse the job records ({e})"))
+    }
+
+    /// Find the latest job record of a local file
+    pub fn find(path: &str) -> Result<Option<Job>> {
+        let path = Self::key(path);
+        Ok(Self::load()?.into_iter().rev().find(|j| j.path == path))
+    }
+
+    /// Save the job record, replacing the previous record of the same file
+    pub fn save(&self) -> Result<()> {
+        let mut jobs = Self::load()?;
+        jobs.retain(|j| j.path != self.path);
+        jobs.push(self.clone());
+        Self::write(&jobs)
+    }
+
+    /// Update the records of a local file that has been moved
+    pub fn relocate(from: &str, to: &str) -> Result<()> {
+        let (from, to) = (Self::key(from), Self::key(to));
+        let mut jobs = Self::load()?;
+        jobs.iter_mut()
+            .filter(|j| j.path == from)
+            .for_each(|j| j.path = to.clone());
+        Self::write(&jobs)
+    }
+
+    /// Write the job records
+    fn write(jobs: &[Job]) -> Result<()> {
+        let jobs = serde_json::to_string_pretty(jobs)
+            .map_err(|e| eyre!("Unable to save the job records ({e})"))?;
+        fs::write(Self::records_path()?, jobs)
+            .map_err(|e| eyre!("Unable to save the job records ({e})"))
+    }
+
+    /// Absolute path used to identify a local file
+    fn key(path: &str) -> String {
+        fs::canonicalize(path)
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_else(|_| path.to_string())
+    }
+
+    /// Path of the job records
+    fn records_path() -> Result<PathBuf> {
+        let dir = ProjectDirs::from("dev", "latruiterouge", "downtobox")
+            .ok_or_else(|| eyre!("The data path cannot be determined"))?;
+        fs::create_dir_all(dir.data_dir())
+            .map_err(|e| eyre!("Unable to create the data directory ({e})"))?;
+        Ok(dir.data_dir().join("jobs.json"))
+    }
+}
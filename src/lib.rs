@@ -0,0 +1,44 @@
+//! Download files from Uptobox, rename them from their tracks and upload them back
+//!
+//! The [`Pipeline`] runs the backup and upload jobs of the command line, the other
+//! modules expose each of its stages.
+
+use color_eyre::eyre::eyre;
+pub use color_eyre::eyre::{Report, Result};
+
+pub mod account;
+pub mod api;
+pub mod checksum;
+pub mod cleanup;
+pub mod command;
+pub mod config;
+pub mod dedupe;
+pub mod download;
+pub mod file;
+pub mod interrupt;
+pub mod job;
+pub mod limit;
+pub mod matroska;
+pub mod pipeline;
+pub mod remote;
+pub mod secret;
+pub mod serve;
+pub mod setup;
+pub mod space;
+pub mod storage;
+pub mod title;
+pub mod upload;
+pub mod uptobox;
+pub mod verify;
+pub mod webdav;
+
+pub use api::UptoboxApi;
+pub use config::Config;
+pub use download::Download;
+pub use file::File;
+pub use limit::Limiters;
+pub use matroska::Matroska;
+pub use pipeline::{Event, Pipeline, Tags, UploadType};
+pub use storage::RemoteStorage;
+pub use title::Title;
+pub use upload::Upload;
@@ -0,0 +1,227 @@
+use chrono::{Local, NaiveTime};
+use std::{sync::Arc, time::Duration};
+use tokio::{
+    sync::Mutex,
+    time::{sleep, Instant},
+};
+
+use crate::{
+    config::{LimitSchedule, Limits},
+    eyre, Result,
+};
+
+/// Bandwidth limiters shared by every transfer
+pub struct Limiters {
+    pub download: Arc<Limiter>,
+    pub upload: Arc<Limiter>,
+}
+
+impl Limiters {
+    /// The command line limits replace the configured limits and schedule
+    pub fn new(
+        limits: &Limits,
+        download: &Option<String>,
+        upload: &Option<String>,
+    ) -> Result<Self> {
+        let download = match download {
+            Some(rate) => Limiter::new(Self::parse_rate(rate)?, vec![]),
+            None => Limiter::new(
+                Self::parse_rate(&limits.download)?,
+                Self::parse_schedule(&limits.schedule, |s| &s.download)?,
+            ),
+        };
+        let upload = match upload {
+            Some(rate) => Limiter::new(Self::parse_rate(rate)?, vec![]),
+            None => Limiter::new(
+                Self::parse_rate(&limits.upload)?,
+                Self::parse_schedule(&limits.schedule, |s| &s.upload)?,
+            ),
+        };
+
+        Ok(Self {
+            download: Arc::new(download),
+            upload: Arc::new(upload),
+        })
+    }
+
+    /// Parse a rate in bytes per second with an optional K, M or G suffix (0 when unlimited)
+    ///
+    /// The suffixes are binary multiples, '5M', '5MB' and '5MiB' are the same rate.
+    fn parse_rate(rate: &str) -> Result<u64> {
+        let rate = rate.trim();
+        if rate.is_empty() {
+            return Ok(0);
+        }
+
+        let (value, unit) = rate.split_at(
+            rate.find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rate.len()),
+        );
+        let multiplier: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+            "" | "B" => 1,
+            "K" | "KB" | "KIB" => 1024,
+            "M" | "MB" | "MIB" => 1024 * 1024,
+            "G" | "GB" | "GIB" => 1024 * 1024 * 1024,
+            _ => return Err(eyre!("Unable to parse the rate '{rate}' (unknown unit)")),
+        };
+        let value: u64 = value
+            .parse()
+            .map_err(|e| eyre!("Unable to parse the rate '{rate}' ({e})"))?;
+
+        value
+            .checked_mul(multiplier)
+            .ok_or_else(|| eyre!("Unable to parse the rate '{rate}' (too large)"))
+    }
+
+    /// Parse the time windows of one direction
+    fn parse_schedule(
+        schedule: &[LimitSchedule],
+        rate: fn(&LimitSchedule) -> &String,
+    ) -> Result<Vec<Window>> {
+        schedule
+            .iter()
+            .map(|s| {
+                Ok(Window {
+                    start: Self::parse_time(&s.start)?,
+                    end: Self::parse_time(&s.end)?,
+                    rate: Self::parse_rate(rate(s))?,
+                })
+            })
+            .collect()
+    }
+
+    /// Parse a time of day (HH:MM)
+    fn parse_time(time: &str) -> Result<NaiveTime> {
+        NaiveTime::parse_from_str(time.trim(), "%H:%M")
+            .map_err(|e| eyre!("Unable to parse the time '{time}' ({e})"))
+    }
+}
+
+/// Token bucket limiting the bytes transferred per second
+pub struct Limiter {
+    rate: u64,
+    schedule: Vec<Window>,
+    bucket: Mutex<Bucket>,
+}
+
+impl Limiter {
+    fn new(rate: u64, schedule: Vec<Window>) -> Self {
+        Self {
+            rate,
+            schedule,
+            bucket: Mutex::new(Bucket {
+                tokens: 0.0,
+                last: Instant::now(),
+            }),
+        }
+    }
+
+    /// Wait until `bytes` can be transferred
+    pub async fn acquire(&self, bytes: usize) {
+        let rate = self.current_rate();
+        if rate == 0 {
+            return;
+        }
+
+        // The lock is held while waiting so concurrent transfers share the rate
+        let mut bucket = self.bucket.lock().await;
+        let now = Instant::now();
+        let elapsed = now.duration_since(bucket.last).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate as f64).min(rate as f64);
+        bucket.last = now;
+        bucket.tokens -= bytes as f64;
+        if bucket.tokens < 0.0 {
+            sleep(Duration::from_secs_f64(-bucket.tokens / rate as f64)).await;
+        }
+    }
+
+    /// Rate of the current time window
+    fn current_rate(&self) -> u64 {
+        let now = Local::now().time();
+        self.schedule
+            .iter()
+            .find(|w| w.contains(now))
+            .map(|w| w.rate)
+            .unwrap_or(self.rate)
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    last: Instant,
+}
+
+struct Window {
+    start: NaiveTime,
+    end: NaiveTime,
+    rate: u64,
+}
+
+impl Window {
+    /// Check if the time is in the window, which may span midnight
+    fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(time: &str) -> NaiveTime {
+        Limiters::parse_time(time).unwrap()
+    }
+
+    #[test]
+    fn parse_rate_units() {
+        assert_eq!(Limiters::parse_rate("").unwrap(), 0);
+        assert_eq!(Limiters::parse_rate("512").unwrap(), 512);
+        assert_eq!(Limiters::parse_rate("512B").unwrap(), 512);
+        assert_eq!(Limiters::parse_rate("512K").unwrap(), 512 * 1024);
+        assert_eq!(Limiters::parse_rate(" 5m ").unwrap(), 5 * 1024 * 1024);
+        assert_eq!(Limiters::parse_rate("5MB").unwrap(), 5 * 1024 * 1024);
+        assert_eq!(Limiters::parse_rate("5 MiB").unwrap(), 5 * 1024 * 1024);
+        assert_eq!(Limiters::parse_rate("1G").unwrap(), 1024 * 1024 * 1024);
+    }
+
+    #[test]
+    fn parse_rate_errors() {
+        for rate in ["fast", "5T", "5MBps", "-5M", "1.5M"] {
+            assert!(Limiters::parse_rate(rate).is_err(), "{rate}");
+        }
+        let error = Limiters::parse_rate("99999999999G").unwrap_err();
+        assert!(error.to_string().starts_with("Unable to parse the rate"));
+    }
+
+    #[test]
+    fn window_contains() {
+        let day = Window {
+            start: time("08:00"),
+            end: time("20:00"),
+            rate: 0,
+        };
+        assert!(day.contains(time("08:00")));
+        assert!(day.contains(time("12:30")));
+        assert!(!day.contains(time("20:00")));
+        assert!(!day.contains(time("03:00")));
+    }
+
+    #[test]
+    fn window_contains_across_midnight() {
+        let night = Window {
+            start: time("22:00"),
+            end: time("06:00"),
+            rate: 0,
+        };
+        assert!(night.contains(time("22:00")));
+        assert!(night.contains(time("23:59")));
+        assert!(night.contains(time("00:00")));
+        assert!(night.contains(time("05:59")));
+        assert!(!night.contains(time("06:00")));
+        assert!(!night.contains(time("12:00")));
+    }
+}
@@ -1,247 +1,523 @@
 use clap::Parser;
 use color_eyre::eyre::{eyre, Report, Result};
+use indicatif::HumanBytes;
 use reqwest::Client;
-use rs_uptobox::Uptobox as UptoboxApi;
-use std::path::Path;
+use std::{env, fs, path::Path};
+
+use downtobox::{
+    account::Account,
+    checksum::Checksum,
+    command::Command,
+    config::{Config, LocalPolicy, Storage, SuffixMode},
+    dedupe::Dedupe,
+    interrupt::Interrupt,
+    job::Job,
+    remote::Remote,
+    serve::Serve,
+    setup::Setup,
+    space::Space,
+    storage::{self, RemoteFile, RemoteStorage},
+    Download, File, Limiters, Pipeline, Title, UploadType,
+};
 
 mod cli;
-mod command;
-mod config;
-mod download;
-mod file;
-mod matroska;
-mod title;
-mod upload;
-mod uptobox;
+mod progress;
 
 use cli::*;
-use command::Command;
-use config::Config;
-use download::Download;
-use file::File;
-use title::Title;
-use upload::Upload;
-use uptobox::Uptobox;
-
-enum UploadType {
-    Show,
-    Film,
-}
+use progress::Progress;
 
 #[tokio::main]
 async fn main() -> Result<(), Report> {
     color_eyre::install()?;
 
+    let args = Cli::parse();
+
+    let overrides = args.overrides.pairs();
+    let client = Client::new();
+
+    // The config commands work with a missing or invalid config
+    if let CliSubCmd::Config(cmd) = &args.subcmd {
+        let (path, profile) = (args.config.as_deref(), args.profile.as_deref());
+        return match &cmd.subcmd {
+            CliConfigCmd::Init => Setup::init(&client, path, &overrides).await,
+            CliConfigCmd::Check => Setup::check(&client, path, profile, &overrides).await,
+            CliConfigCmd::Profiles => list_profiles(path, profile, &overrides),
+            CliConfigCmd::SetKey(key) => Setup::set_key(path, &key.service, key.file.as_deref()),
+        };
+    }
+
     Command::check()?;
 
-    let config = Config::init()?;
+    let config = Config::init(args.config.as_deref(), args.profile.as_deref(), &overrides)?;
 
-    let args = Cli::parse();
+    let remote = storage::connect(client.clone(), &config);
+    let storage = remote.as_ref();
+    let limiters = Limiters::new(&config.limits, &args.limit_down, &args.limit_up)?;
 
-    let api_key: &'static str = Box::leak(config.api_key.clone().into_boxed_str());
+    let progress = Progress::default();
 
-    let uptobox = UptoboxApi::new(api_key);
-    let client = Client::new();
+    Interrupt::install();
+
+    // Only premium accounts can use this software, checked once per run
+    if matches!(
+        args.subcmd,
+        CliSubCmd::Backup(_)
+            | CliSubCmd::Upload(_)
+            | CliSubCmd::Sync(_)
+            | CliSubCmd::Retitle(_)
+            | CliSubCmd::ServeWebdav(_)
+    ) {
+        Account::check(storage, config.premium_warning).await?;
+    }
 
     match args.subcmd {
         CliSubCmd::Backup(backup) => match backup.subcmd {
             CliBackupCmd::Show(show) => {
-                for link in &show.links {
-                    backup_files(
-                        (&uptobox, &client, &config),
-                        &show,
-                        &UploadType::Show,
-                        link.trim(),
-                    )
-                    .await?;
+                let (links, deferred) = batch_links((storage, &config), &show).await?;
+                let pipeline = Pipeline::new(
+                    (storage, &client, &config, &limiters),
+                    UploadType::Show,
+                    &show.title,
+                )
+                .tags(&show.tags)
+                .keep_local(show.keep_local)
+                .on_event(move |e| progress.report(e));
+                for (i, link) in links.iter().enumerate() {
+                    Interrupt::stop(&links[i..])?;
+                    pipeline
+                        .backup(link.trim())
+                        .await
+                        .map_err(|e| Interrupt::pending(e, &links[i..]))?;
                 }
+                report_deferred(&config, &deferred)?;
             }
             CliBackupCmd::Film(film) => {
-                for link in &film.links {
-                    backup_files(
-                        (&uptobox, &client, &config),
-                        &film,
-                        &UploadType::Film,
-                        link.trim(),
-                    )
-                    .await?;
+                let (links, deferred) = batch_links((storage, &config), &film).await?;
+                let pipeline = Pipeline::new(
+                    (storage, &client, &config, &limiters),
+                    UploadType::Film,
+                    &film.title,
+                )
+                .tags(&film.tags)
+                .keep_local(film.keep_local)
+                .on_event(move |e| progress.report(e));
+                for (i, link) in links.iter().enumerate() {
+                    Interrupt::stop(&links[i..])?;
+                    pipeline
+                        .backup(link.trim())
+                        .await
+                        .map_err(|e| Interrupt::pending(e, &links[i..]))?;
                 }
+                report_deferred(&config, &deferred)?;
             }
         },
         CliSubCmd::Upload(upload) => match upload.subcmd {
             CliUploadCmd::Show(show) => {
-                for path in &show.paths {
-                    upload_files(
-                        (&uptobox, &client, &config),
-                        &show,
-                        &UploadType::Show,
-                        path.trim(),
-                    )
-                    .await?;
+                let pipeline = Pipeline::new(
+                    (storage, &client, &config, &limiters),
+                    UploadType::Show,
+                    &show.title,
+                )
+                .tags(&show.tags)
+                .on_event(move |e| progress.report(e));
+                for (i, path) in show.paths.iter().enumerate() {
+                    Interrupt::stop(&show.paths[i..])?;
+                    pipeline
+                        .upload(path.trim())
+                        .await
+                        .map_err(|e| Interrupt::pending(e, &show.paths[i..]))?;
                 }
             }
             CliUploadCmd::Film(film) => {
-                for path in &film.paths {
-                    upload_files(
-                        (&uptobox, &client, &config),
-                        &film,
-                        &UploadType::Film,
-                        path.trim(),
-                    )
-                    .await?;
+                let pipeline = Pipeline::new(
+                    (storage, &client, &config, &limiters),
+                    UploadType::Film,
+                    &film.title,
+                )
+                .tags(&film.tags)
+                .on_event(move |e| progress.report(e));
+                for (i, path) in film.paths.iter().enumerate() {
+                    Interrupt::stop(&film.paths[i..])?;
+                    pipeline
+                        .upload(path.trim())
+                        .await
+                        .map_err(|e| Interrupt::pending(e, &film.paths[i..]))?;
+                }
+            }
+        },
+        CliSubCmd::Sync(sync) => match sync.subcmd {
+            CliSyncCmd::Show(show) => {
+                let pipeline = Pipeline::new(
+                    (storage, &client, &config, &limiters),
+                    UploadType::Show,
+                    &show.title,
+                )
+                .tags(&show.tags)
+                .on_event(move |e| progress.report(e));
+                sync_files((storage, &config, &pipeline), &show).await?
+            }
+            CliSyncCmd::Film(film) => {
+                let pipeline = Pipeline::new(
+                    (storage, &client, &config, &limiters),
+                    UploadType::Film,
+                    &film.title,
+                )
+                .tags(&film.tags)
+                .on_event(move |e| progress.report(e));
+                sync_files((storage, &config, &pipeline), &film).await?
+            }
+        },
+        CliSubCmd::Retitle(retitle) => match retitle.subcmd {
+            CliRetitleCmd::Show(show) => {
+                let pipeline = Pipeline::new(
+                    (storage, &client, &config, &limiters),
+                    UploadType::Show,
+                    &show.title,
+                )
+                .tags(&show.tags);
+                for (i, link) in show.links.iter().enumerate() {
+                    Interrupt::stop(&show.links[i..])?;
+                    retitle_files((storage, &client, &config, &pipeline), &show, link.trim())
+                        .await
+                        .map_err(|e| Interrupt::pending(e, &show.links[i..]))?;
+                }
+            }
+            CliRetitleCmd::Film(film) => {
+                let pipeline = Pipeline::new(
+                    (storage, &client, &config, &limiters),
+                    UploadType::Film,
+                    &film.title,
+                )
+                .tags(&film.tags);
+                for (i, link) in film.links.iter().enumerate() {
+                    Interrupt::stop(&film.links[i..])?;
+                    retitle_files((storage, &client, &config, &pipeline), &film, link.trim())
+                        .await
+                        .map_err(|e| Interrupt::pending(e, &film.links[i..]))?;
                 }
             }
         },
+        CliSubCmd::Verify(verify) => verify_files(&verify.paths)?,
+        CliSubCmd::Dedupe(dedupe) => {
+            Dedupe::start(
+                storage,
+                &dedupe.path,
+                &config.suffix,
+                dedupe.keep,
+                dedupe.any_size,
+                (dedupe.delete, dedupe.yes),
+            )
+            .await?
+        }
+        // Handled before loading the config
+        CliSubCmd::Config(_) => (),
+        CliSubCmd::Account => Account::show(storage, config.storage_quota).await?,
+        CliSubCmd::ServeWebdav(serve) => {
+            Serve::webdav(
+                remote.clone(),
+                client.clone(),
+                &serve.address,
+                &serve.path,
+                serve.redirect,
+            )
+            .await?
+        }
+        CliSubCmd::Remote(remote) => match remote.subcmd {
+            CliRemoteCmd::Ls(ls) => Remote::ls(storage, &ls.path, ls.recursive, ls.json).await?,
+            CliRemoteCmd::Mkdir(mkdir) => {
+                Remote::mkdir(storage, &mkdir.path, mkdir.parents).await?
+            }
+            CliRemoteCmd::Mv(mv) => Remote::mv(storage, &mv.source, &mv.destination).await?,
+            CliRemoteCmd::Rm(rm) => Remote::rm(storage, &rm.paths, rm.recursive).await?,
+            CliRemoteCmd::Rename(rename) => {
+                Remote::rename(storage, &rename.path, &rename.name).await?
+            }
+        },
     };
 
     Ok(())
 }
 
-/// Backup files section
-async fn backup_files(
-    (uptobox, client, config): (&UptoboxApi, &Client, &Config),
+/// Split a batch of downloads between the links fitting on the disk and the deferred ones
+async fn batch_links(
+    (storage, config): (&dyn RemoteStorage, &Config),
     args: &CliBackup,
-    upload_type: &UploadType,
-    link: &str,
-) -> Result<()> {
-    // Only premium accounts can use this software
-    check_uptobox_account(uptobox).await?;
-
-    // File informations
-    let file: File = File::new(uptobox, link, &config.local_path).await?;
-
-    // Check if it is a matroska file
-    check_file(&file.name)?;
-
-    // Check if the episode can be parsed online
-    if let UploadType::Show = upload_type {
-        Title::get_episode(&file.path)?;
-    }
-
-    // Download the file
-    Download::start(client, &file.url, &file.path).await?;
-
-    // Generate the file name
-    let title = match upload_type {
-        UploadType::Show => Title::generate_show_title(
-            &file.path,
-            &file.name,
-            &args.title,
-            &args.languages,
-            &args.sources,
-        )?,
-        UploadType::Film => {
-            Title::generate_film_title(&file.path, &args.title, &args.languages, &args.sources)?
+) -> Result<(Vec<String>, Vec<(String, u64)>)> {
+    let mut free = Space::free(&config.local_path, config.disk_margin)?;
+
+    // Deleted files free their space before the next download
+    let kept = !matches!(
+        (&config.local_policy, args.keep_local),
+        (LocalPolicy::Delete, false)
+    );
+
+    let (mut links, mut deferred) = (vec![], vec![]);
+    for link in &args.links {
+        let size = File::size(storage, link.trim()).await?;
+        if size > free {
+            deferred.push((link.clone(), size));
+            continue;
         }
-    };
+        if kept {
+            free -= size;
+        }
+        links.push(link.clone());
+    }
+
+    if !deferred.is_empty() {
+        eprintln!(
+            "{} download(s) deferred, not enough free space in '{}'",
+            deferred.len(),
+            config.local_path
+        );
+    }
+    Ok((links, deferred))
+}
 
-    // Update the title
-    Command::update_title(&file.path, &title, true)?;
+/// Print the deferred downloads of a batch once the others are done
+fn report_deferred(config: &Config, deferred: &[(String, u64)]) -> Result<()> {
+    if deferred.is_empty() {
+        return Ok(());
+    }
+    eprintln!("\nDeferred:");
+    for (link, size) in deferred {
+        eprintln!("  {} ({})", link.trim(), HumanBytes(*size));
+    }
+    Err(eyre!(
+        "{} download(s) did not fit in '{}', run them again once space is freed",
+        deferred.len(),
+        config.local_path
+    ))
+}
 
-    // Upload the file
-    println!("\n\nUploading '{}'\n", title);
-    Upload::start(client, uptobox, &file.path, &title).await?;
+/// Sync files section
+async fn sync_files(
+    (storage, config, pipeline): (&dyn RemoteStorage, &Config, &Pipeline<'_>),
+    args: &CliSync,
+) -> Result<()> {
+    // Generate the file name of every local file
+    let mut local = vec![];
+    for path in local_files(Path::new(&args.dir))? {
+        let file_name = Path::new(&path)
+            .file_name()
+            .ok_or_else(|| eyre!("Unable to extract file name"))?
+            .to_string_lossy()
+            .to_string();
+        let title = pipeline.title(&path, &file_name)?;
+        local.push((path, title));
+    }
 
-    // Get the files
-    let files = Uptobox::get_files(uptobox).await?;
+    // Compare with the destination folder, only the files of this title may be deleted
+    let remote = storage.list(&config.destination_path).await?.files;
+    let missing: Vec<&(String, String)> = local
+        .iter()
+        .filter(|(_, title)| {
+            !remote
+                .iter()
+                .any(|f| Title::same_name(&config.suffix, &f.file_name, title))
+        })
+        .collect();
+    let extras: Vec<&RemoteFile> = match args.delete {
+        true => remote
+            .iter()
+            .filter(|f| {
+                !local
+                    .iter()
+                    .any(|(_, title)| Title::same_name(&config.suffix, &f.file_name, title))
+            })
+            .filter_map(|f| match pipeline.owns(&f.file_name) {
+                Ok(true) => Some(Ok(f)),
+                Ok(false) => None,
+                Err(e) => Some(Err(e)),
+            })
+            .collect::<Result<_>>()?,
+        false => vec![],
+    };
 
-    // Get the uploaded file
-    let uploaded_file = Uptobox::get_uploaded_file(&files, &title)?;
+    // Preview the plan
+    if missing.is_empty() && extras.is_empty() {
+        println!(
+            "'{}' is in sync with '{}'",
+            args.dir, config.destination_path
+        );
+        return Ok(());
+    }
+    for (path, title) in &missing {
+        println!("upload  {path} -> {title}");
+    }
+    if !extras.is_empty() {
+        println!(
+            "Files of '{}' in '{}' missing from '{}':",
+            args.title, config.destination_path, args.dir
+        );
+    }
+    for file in &extras {
+        println!("delete  {}", file.file_name);
+    }
+    if args.dry_run {
+        return Ok(());
+    }
+    if !args.yes && !Remote::confirm("Apply the plan?")? {
+        println!("Aborted");
+        return Ok(());
+    }
 
-    // Get destination folder
-    let destination = Uptobox::get_destination_directory(uptobox, &config.destination_path).await?;
+    // Upload the missing files
+    let pending: Vec<String> = missing.iter().map(|(path, _)| path.clone()).collect();
+    for (i, path) in pending.iter().enumerate() {
+        Interrupt::stop(&pending[i..])?;
+        pipeline
+            .upload(path)
+            .await
+            .map_err(|e| Interrupt::pending(e, &pending[i..]))?;
+    }
 
-    // Move file to destination
-    Uptobox::move_files_to_destination(
-        uptobox,
-        &uploaded_file.file_code,
-        destination.current_folder.fld_id,
-    )
-    .await?;
+    // Delete the remote extras
+    if !extras.is_empty() {
+        let codes: Vec<&str> = extras.iter().map(|f| f.file_code.as_str()).collect();
+        storage.delete_files(&codes).await?;
+        println!("Deleted {} remote file(s)", codes.len());
+    }
 
     Ok(())
 }
 
-/// Upload files section
-async fn upload_files(
-    (uptobox, client, config): (&UptoboxApi, &Client, &Config),
-    args: &CliUpload,
-    upload_type: &UploadType,
-    path: &str,
+/// Retitle files section
+async fn retitle_files(
+    (storage, client, config, pipeline): (&dyn RemoteStorage, &Client, &Config, &Pipeline<'_>),
+    args: &CliRetitle,
+    link: &str,
 ) -> Result<()> {
-    // Only premium accounts can use downtobox
-    check_uptobox_account(uptobox).await?;
+    let _stage = Interrupt::stage();
 
-    let file_name = Path::new(path)
-        .file_name()
-        .ok_or_else(|| eyre!("Unable to extract file name"))?
-        .to_str()
-        .ok_or_else(|| eyre!("Unable to extract file name"))?;
+    // File informations
+    let file: File = File::new(storage, link, &config.local_path).await?;
 
     // Check if it is a matroska file
-    check_file(file_name)?;
-
-    // Generate the file name
-    let title = match upload_type {
-        UploadType::Show => Title::generate_show_title(
-            path,
-            file_name,
-            &args.title,
-            &args.languages,
-            &args.sources,
-        )?,
-        UploadType::Film => {
-            Title::generate_film_title(path, &args.title, &args.languages, &args.sources)?
+    File::check_name(&file.name)?;
+
+    // The suffix of the file name is kept, a hash suffix needs the whole file
+    let current = Title::strip_suffix(&config.suffix, &file.name)
+        .map(|stem| file.name.trim_end_matches(".mkv")[stem.len() + 1..].to_string());
+    let suffix = match (&config.suffix.mode, config.suffix.file_name, current) {
+        (_, false, _) => None,
+        (_, true, Some(current)) => Some(current),
+        (SuffixMode::Hash, true, None) => {
+            return Err(eyre!(
+            "Unable to give a hash suffix to '{}' without downloading the whole file (suffix.mode)",
+            file.name
+        ))
         }
+        (_, true, None) => Title::suffix(&config.suffix, "")?,
     };
 
-    // Update the title
-    Command::update_title(path, &title, true)?;
-
-    // Upload the file
-    println!("\n\nUploading '{}'\n", title);
-    Upload::start(client, uptobox, path, &title).await?;
+    // Download the head of the file to read its tracks
+    let head = env::temp_dir()
+        .join(format!("downtobox-{}.mkv", file.code))
+        .to_string_lossy()
+        .to_string();
+    let title =
+        match Download::head((client, storage), &file.url, &head, args.head * 1024 * 1024).await {
+            // Generate the file name
+            Ok(_) => pipeline.title(&head, &file.name),
+            Err(e) => Err(e),
+        };
+
+    // The head is removed whatever happened, even when partially downloaded
+    if Path::new(&head).exists() {
+        fs::remove_file(&head).map_err(|e| eyre!("Unable to remove '{head}' ({e})"))?;
+    }
+    let title = match suffix {
+        Some(suffix) => Title::suffixed_name(&title?, &suffix),
+        None => title?,
+    };
 
-    // Get the files
-    let files = Uptobox::get_files(uptobox).await?;
+    if title == file.name {
+        println!("'{}' is already titled", file.name);
+        return Ok(());
+    }
+    println!("'{}' -> '{title}'", file.name);
 
-    // Get the uploaded file
-    let uploaded_file = Uptobox::get_uploaded_file(&files, &title)?;
+    // Rename the remote file
+    if !args.dry_run {
+        storage.rename_file(&file.code, &title).await?;
+    }
 
-    // Get destination folder
-    let destination = Uptobox::get_destination_directory(uptobox, &config.destination_path).await?;
+    Ok(())
+}
 
-    // Move file to destination
-    Uptobox::move_files_to_destination(
-        uptobox,
-        &uploaded_file.file_code,
-        destination.current_folder.fld_id,
-    )
-    .await?;
+/// Matroska files of a local directory and its sub directories
+fn local_files(dir: &Path) -> Result<Vec<String>> {
+    let mut files = vec![];
+    let entries =
+        fs::read_dir(dir).map_err(|e| eyre!("Unable to read '{}' ({e})", dir.display()))?;
+    for entry in entries {
+        let path = entry
+            .map_err(|e| eyre!("Unable to read '{}' ({e})", dir.display()))?
+            .path();
+        if path.is_dir() {
+            files.extend(local_files(&path)?);
+        } else if path.extension().is_some_and(|e| e == "mkv") {
+            files.push(path.to_string_lossy().to_string());
+        }
+    }
+    files.sort();
+    Ok(files)
+}
 
+/// Profiles section
+fn list_profiles(
+    path: Option<&str>,
+    active: Option<&str>,
+    overrides: &[(&str, String)],
+) -> Result<()> {
+    let table = Config::read(path)?;
+    let profiles = Config::profiles(&table);
+    if profiles.is_empty() {
+        println!("No profile defined");
+    }
+    for name in profiles {
+        // The secrets are not needed to list the profiles
+        let config = Config::load_unresolved(table.clone(), Some(&name), overrides)?;
+        let backend = match config.storage {
+            Storage::Uptobox => "uptobox".to_string(),
+            Storage::Webdav { url, .. } => format!("webdav {url}"),
+        };
+        let marker = if active == Some(name.as_str()) {
+            "*"
+        } else {
+            " "
+        };
+        println!("{marker} {name}: {} ({backend})", config.destination_path);
+    }
     Ok(())
 }
 
-/// Check if the file is a matroska
-fn check_file(name: &str) -> Result<()> {
-    if !name.ends_with(".mkv") {
-        Err(eyre!(
-            "This software is only compatible with matroska (.mkv) files"
-        ))
-    } else {
-        Ok(())
+/// Verify files section
+fn verify_files(paths: &[String]) -> Result<()> {
+    let mut failed = 0;
+    for path in paths {
+        let path = path.trim();
+
+        // Get the recorded checksum
+        let expected = match Job::find(path)? {
+            Some(job) => Some(job.sha256),
+            None => Checksum::read_sidecar(path)?,
+        }
+        .ok_or_else(|| eyre!("No checksum recorded for '{path}'"))?;
+
+        if Checksum::file(path)? == expected {
+            println!("{path}: OK");
+        } else {
+            println!("{path}: FAILED");
+            failed += 1;
+        }
     }
-}
 
-/// Check if the uptobox account is premium
-async fn check_uptobox_account(uptobox: &UptoboxApi) -> Result<()> {
-    let uptobox_account = uptobox
-        .get_account()
-        .await
-        .map_err(|e| eyre!("Unable to retreive your uptobox account ({e})"))?;
-    if uptobox_account.premium {
-        Ok(())
-    } else {
-        Err(eyre!("A premium account is needed to use this software"))
+    match failed {
+        0 => Ok(()),
+        _ => Err(eyre!(
+            "{failed} file(s) did not match the recorded checksum"
+        )),
     }
 }
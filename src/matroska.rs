@@ -31,7 +31,7 @@ impl Matroska {
         Ok(format!(
             "{}",
             self.videos
-                .get(0)
+                .first()
                 .ok_or_else(|| eyre!("Unable to retreive matroska resolution"))?
                 .properties
                 .resolution
@@ -242,6 +242,75 @@ impl fmt::Display for MatroskaSource {
     }
 }
 
+/// Streaming service a WEB release comes from
+pub enum MatroskaService {
+    Amazon,
+    Netflix,
+    Disney,
+    ITunes,
+    None,
+}
+
+impl From<&str> for MatroskaService {
+    fn from(value: &str) -> Self {
+        match value.to_lowercase().as_str() {
+            "amzn" | "amazon" => Self::Amazon,
+            "nf" | "netflix" => Self::Netflix,
+            "dsnp" | "disney" | "disney+" => Self::Disney,
+            "it" | "itunes" => Self::ITunes,
+            _ => Self::None,
+        }
+    }
+}
+
+impl fmt::Display for MatroskaService {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self {
+            Self::Amazon => write!(f, "AMZN"),
+            Self::Netflix => write!(f, "NF"),
+            Self::Disney => write!(f, "DSNP"),
+            Self::ITunes => write!(f, "iT"),
+            Self::None => write!(f, ""),
+        }
+    }
+}
+
+/// Edition or re-release marker
+pub enum MatroskaEdition {
+    Repack,
+    Proper,
+    Extended,
+    DirectorsCut,
+    Imax,
+    None,
+}
+
+impl From<&str> for MatroskaEdition {
+    fn from(value: &str) -> Self {
+        match value.to_lowercase().as_str() {
+            "repack" => Self::Repack,
+            "proper" => Self::Proper,
+            "extended" => Self::Extended,
+            "dc" | "directors-cut" | "directorscut" => Self::DirectorsCut,
+            "imax" => Self::Imax,
+            _ => Self::None,
+        }
+    }
+}
+
+impl fmt::Display for MatroskaEdition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self {
+            Self::Repack => write!(f, "REPACK"),
+            Self::Proper => write!(f, "PROPER"),
+            Self::Extended => write!(f, "EXTENDED"),
+            Self::DirectorsCut => write!(f, "DC"),
+            Self::Imax => write!(f, "IMAX"),
+            Self::None => write!(f, ""),
+        }
+    }
+}
+
 pub enum MatroskaLang {
     MULTi,
     VOSTfr,
@@ -0,0 +1,317 @@
+use reqwest::Client;
+use std::path::Path;
+
+use crate::{
+    checksum::Checksum, cleanup::Cleanup, command::Command, config::Config, download::Download,
+    eyre, file::File, interrupt::Interrupt, job::Job, limit::Limiters, space::Space,
+    storage::RemoteStorage, title::Title, upload::Upload, uptobox::Uptobox, verify::Verify, Result,
+};
+
+/// Kind of media handled by a pipeline
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UploadType {
+    Show,
+    Film,
+}
+
+/// Progress events reported while a job runs
+#[derive(Debug)]
+pub enum Event<'a> {
+    /// The download of a file starts
+    Download {
+        url: &'a str,
+        path: &'a str,
+        size: u64,
+    },
+    /// Bytes downloaded so far
+    Progress { downloaded: u64, size: u64 },
+    /// The file is downloaded
+    Downloaded { url: &'a str, path: &'a str },
+    /// The file is uploaded under its new title
+    Upload { path: &'a str, title: &'a str },
+    /// The uploaded file matches the local file
+    Verified { file_code: &'a str },
+    /// The file is in the destination folder
+    Done { title: &'a str, file_code: &'a str },
+}
+
+/// Tags of the generated titles, the services, editions and release group are detected from
+/// the file names when not given
+#[derive(Debug, Default, Clone)]
+pub struct Tags {
+    pub languages: String,
+    pub sources: String,
+    pub services: String,
+    pub editions: String,
+    /// Release group replacing the one of the file names, dropped when empty
+    pub group: Option<String>,
+}
+
+/// Backup and upload jobs sharing the same title and tags
+pub struct Pipeline<'a> {
+    storage: &'a dyn RemoteStorage,
+    client: &'a Client,
+    config: &'a Config,
+    limiters: &'a Limiters,
+    upload_type: UploadType,
+    title: String,
+    tags: Tags,
+    keep_local: bool,
+    on_event: Box<dyn Fn(Event) + Send + Sync + 'a>,
+}
+
+impl<'a> Pipeline<'a> {
+    pub fn new(
+        (storage, client, config, limiters): (
+            &'a dyn RemoteStorage,
+            &'a Client,
+            &'a Config,
+            &'a Limiters,
+        ),
+        upload_type: UploadType,
+        title: &str,
+    ) -> Self {
+        Self {
+            storage,
+            client,
+            config,
+            limiters,
+            upload_type,
+            title: title.to_owned(),
+            tags: Tags::default(),
+            keep_local: false,
+            on_event: Box::new(|_| {}),
+        }
+    }
+
+    /// Every tag of the generated titles
+    pub fn tags(mut self, tags: impl Into<Tags>) -> Self {
+        self.tags = tags.into();
+        self
+    }
+
+    /// Languages added to the generated titles
+    pub fn languages(mut self, languages: &str) -> Self {
+        self.tags.languages = languages.to_owned();
+        self
+    }
+
+    /// Sources added to the generated titles
+    pub fn sources(mut self, sources: &str) -> Self {
+        self.tags.sources = sources.to_owned();
+        self
+    }
+
+    /// Streaming services added to the generated titles, detected from the file names by default
+    pub fn services(mut self, services: &str) -> Self {
+        self.tags.services = services.to_owned();
+        self
+    }
+
+    /// Editions added to the generated titles, detected from the file names by default
+    pub fn editions(mut self, editions: &str) -> Self {
+        self.tags.editions = editions.to_owned();
+        self
+    }
+
+    /// Release group of the generated titles, kept from the file names when `None` and dropped when empty
+    pub fn group(mut self, group: Option<&str>) -> Self {
+        self.tags.group = group.map(str::to_owned);
+        self
+    }
+
+    /// Keep the downloaded files regardless of the local policy
+    pub fn keep_local(mut self, keep_local: bool) -> Self {
+        self.keep_local = keep_local;
+        self
+    }
+
+    /// Callback receiving the progress events
+    pub fn on_event(mut self, on_event: impl Fn(Event) + Send + Sync + 'a) -> Self {
+        self.on_event = Box::new(on_event);
+        self
+    }
+
+    /// Download a file from its link, rename it and upload it to the destination
+    pub async fn backup(&self, link: &str) -> Result<()> {
+        let (storage, config) = (self.storage, self.config);
+        let _stage = Interrupt::stage();
+
+        // Check the title tags before downloading
+        Title::get_langs(&config.aliases, &self.tags.languages)?;
+        Title::get_source(&config.aliases, &self.tags.sources)?;
+        Title::get_services(&config.aliases, &self.tags.services, "")?;
+        Title::get_editions(&config.aliases, &self.tags.editions, "")?;
+
+        // File informations
+        let file: File = File::new(storage, link, &config.local_path).await?;
+
+        // Check if it is a matroska file
+        File::check_name(&file.name)?;
+
+        // Check if the episode can be parsed online
+        if let UploadType::Show = self.upload_type {
+            Title::get_episode(&file.path)?;
+        }
+
+        // Check if the file fits on the disk
+        Space::check(&file.dir, file.size, config.disk_margin)?;
+
+        // Download the file
+        (self.on_event)(Event::Download {
+            url: &file.url,
+            path: &file.path,
+            size: file.size,
+        });
+        let downloaded = Download::start(
+            (self.client, storage),
+            &self.limiters.download,
+            &file.url,
+            &file.path,
+            |downloaded, size| (self.on_event)(Event::Progress { downloaded, size }),
+        )
+        .await?;
+        (self.on_event)(Event::Downloaded {
+            url: &file.url,
+            path: &file.path,
+        });
+        Job::download(&file.path, &file.code, &downloaded)?.save()?;
+
+        // Rename, upload and move the file
+        let title = self.title(&file.path, &file.name)?;
+        let (title, checksum, file_code) = self.publish(&file.path, &title).await?;
+
+        // Save the job record
+        Job::new(&file.path, &title, &file_code, &checksum, Some(downloaded))?.save()?;
+
+        // Apply the local policy
+        if !self.keep_local {
+            Cleanup::start(&config.local_policy, &file.path, &file.dir)?;
+        }
+
+        (self.on_event)(Event::Done {
+            title: &title,
+            file_code: &file_code,
+        });
+
+        Ok(())
+    }
+
+    /// Rename a local file and upload it to the destination
+    pub async fn upload(&self, path: &str) -> Result<()> {
+        let _stage = Interrupt::stage();
+        let file_name = Path::new(path)
+            .file_name()
+            .ok_or_else(|| eyre!("Unable to extract file name"))?
+            .to_str()
+            .ok_or_else(|| eyre!("Unable to extract file name"))?;
+
+        // Check if it is a complete matroska file
+        File::check_local(path)?;
+        File::check_name(file_name)?;
+
+        // Rename, upload and move the file
+        let title = self.title(path, file_name)?;
+        let (title, checksum, file_code) = self.publish(path, &title).await?;
+
+        // Save the job record
+        Job::new(path, &title, &file_code, &checksum, None)?.save()?;
+
+        (self.on_event)(Event::Done {
+            title: &title,
+            file_code: &file_code,
+        });
+
+        Ok(())
+    }
+
+    /// Generate the title of a file from its tracks
+    pub fn title(&self, path: &str, name: &str) -> Result<String> {
+        match self.upload_type {
+            UploadType::Show => {
+                Title::generate_show_title(self.config, path, name, &self.title, self.title_tags())
+            }
+            UploadType::Film => {
+                Title::generate_film_title(self.config, path, name, &self.title, self.title_tags())
+            }
+        }
+    }
+
+    /// Check if a file name is one the pipeline generates for its title
+    pub fn owns(&self, name: &str) -> Result<bool> {
+        Title::generated_from(
+            self.config,
+            &self.title,
+            name,
+            self.upload_type == UploadType::Show,
+        )
+    }
+
+    /// Languages, sources, services, editions and release group of the titles
+    fn title_tags(&self) -> (&str, &str, &str, &str, Option<&str>) {
+        (
+            &self.tags.languages,
+            &self.tags.sources,
+            &self.tags.services,
+            &self.tags.editions,
+            self.tags.group.as_deref(),
+        )
+    }
+
+    /// Update the title, upload, verify and move the file, returning its name, checksum and file code
+    async fn publish(&self, path: &str, title: &str) -> Result<(String, String, String)> {
+        let (storage, client, config) = (self.storage, self.client, self.config);
+
+        // The title update, upload and move are not interrupted
+        Interrupt::check("the title update")?;
+
+        // Update the title, the file name takes the same suffix
+        let suffix = Title::suffix(&config.suffix, path)?;
+        Command::update_title(path, title, suffix.as_deref())?;
+        let title = match (&suffix, config.suffix.file_name) {
+            (Some(suffix), true) => Title::suffixed_name(title, suffix),
+            _ => title.to_string(),
+        };
+        let title = title.as_str();
+
+        // Upload the file
+        (self.on_event)(Event::Upload { path, title });
+        let checksum = Upload::start(storage, &self.limiters.upload, path, title).await?;
+        if config.sidecar {
+            Checksum::write_sidecar(path, &checksum)?;
+        }
+
+        // Get the files
+        let files = Uptobox::get_files(storage).await?;
+
+        // Get the uploaded file
+        let uploaded_file = Uptobox::get_uploaded_file(&files, title)?;
+
+        // Verify the uploaded file
+        Verify::start(
+            client,
+            storage,
+            &uploaded_file.file_code,
+            path,
+            config.verify_sample,
+        )
+        .await?;
+        (self.on_event)(Event::Verified {
+            file_code: &uploaded_file.file_code,
+        });
+
+        // Get destination folder
+        let destination =
+            Uptobox::get_destination_directory(storage, &config.destination_path).await?;
+
+        // Move file to destination
+        Uptobox::move_files_to_destination(
+            storage,
+            &uploaded_file.file_code,
+            &destination.current_folder,
+        )
+        .await?;
+
+        Ok((title.to_string(), checksum, uploaded_file.file_code.clone()))
+    }
+}
@@ -0,0 +1,44 @@
+use downtobox::Event;
+use indicatif::{ProgressBar, ProgressState, ProgressStyle};
+use std::sync::Mutex;
+
+/// Progress of the pipeline printed on the terminal
+#[derive(Default)]
+pub struct Progress {
+    bar: Mutex<Option<ProgressBar>>,
+}
+
+impl Progress {
+    /// Print a pipeline event
+    pub fn report(&self, event: Event) {
+        let Ok(mut bar) = self.bar.lock() else {
+            return;
+        };
+        match event {
+            Event::Download { url, size, .. } => *bar = Self::set_progress_bar(size, url),
+            Event::Progress { downloaded, .. } => {
+                if let Some(bar) = bar.as_ref() {
+                    bar.set_position(downloaded);
+                }
+            }
+            Event::Downloaded { url, path } => {
+                if let Some(bar) = bar.take() {
+                    bar.finish_with_message(format!("Downloaded {} to {}", url, path));
+                }
+            }
+            Event::Upload { title, .. } => println!("\n\nUploading '{}'\n", title),
+            Event::Verified { .. } | Event::Done { .. } => (),
+        }
+    }
+
+    /// Set the progress bar
+    fn set_progress_bar(size: u64, url: &str) -> Option<ProgressBar> {
+        let pb = ProgressBar::new(size);
+        pb.set_style(ProgressStyle::with_template("{msg}\n {spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({eta}) {bytes_per_sec}")
+            .ok()?
+            .with_key("eta", |state: &ProgressState, w: &mut dyn std::fmt::Write| write!(w, "{:.1}s", state.eta().as_secs_f64()).unwrap())
+            .progress_chars("#>-"));
+        pb.set_message(format!("Downloading {}", url));
+        Some(pb)
+    }
+}
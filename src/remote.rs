@@ -0,0 +1,283 @@
+use indicatif::HumanBytes;
+use serde::Serialize;
+use std::io::{self, Write};
+
+use crate::{
+    eyre,
+    storage::{RemoteFile, RemoteFolder, RemoteStorage},
+    Result,
+};
+
+pub struct Remote {}
+
+/// Remote files management
+impl Remote {
+    /// List a folder
+    pub async fn ls(
+        storage: &dyn RemoteStorage,
+        path: &str,
+        recursive: bool,
+        json: bool,
+    ) -> Result<()> {
+        let mut entries = vec![];
+        let mut folders = vec![Self::normalize(path)];
+        while let Some(folder) = folders.pop() {
+            let listing = storage.list(&folder).await?;
+            let paths: Vec<String> = listing
+                .folders
+                .iter()
+                .map(|f| Self::join(&folder, &Self::folder_name(f)))
+                .collect();
+            if recursive {
+                folders.extend(paths.iter().rev().cloned());
+            }
+            entries.extend(paths.into_iter().map(RemoteEntry::folder));
+            for file in &listing.files {
+                entries.push(RemoteEntry::file(&folder, file));
+            }
+        }
+
+        if json {
+            let output = serde_json::to_string_pretty(&entries)
+                .map_err(|e| eyre!("Unable to serialize the listing ({e})"))?;
+            println!("{output}");
+        } else {
+            Self::print_table(&entries);
+        }
+        Ok(())
+    }
+
+    /// Create a folder, and its parents with `parents`
+    pub async fn mkdir(storage: &dyn RemoteStorage, path: &str, parents: bool) -> Result<()> {
+        let path = Self::normalize(path);
+        if path == "//" {
+            return Err(eyre!("The root folder already exists"));
+        }
+        let segments: Vec<&str> = path.trim_start_matches('/').split('/').collect();
+
+        let mut current = String::from("//");
+        for (i, name) in segments.iter().enumerate() {
+            let last = i == segments.len() - 1;
+            if parents || last {
+                let listing = storage.list(&current).await?;
+                let exists = listing
+                    .folders
+                    .iter()
+                    .any(|f| Self::folder_name(f) == *name);
+                match (exists, last) {
+                    (true, true) if !parents => {
+                        return Err(eyre!("The folder '{path}' already exists"));
+                    }
+                    (true, _) => {}
+                    (false, _) => {
+                        storage.create_folder(&current, name).await?;
+                        println!("Created '{}'", Self::join(&current, name));
+                    }
+                }
+            }
+            current = Self::join(&current, name);
+        }
+        Ok(())
+    }
+
+    /// Move a file or a folder into a folder
+    pub async fn mv(storage: &dyn RemoteStorage, source: &str, destination: &str) -> Result<()> {
+        let destination = storage
+            .list(&Self::normalize(destination))
+            .await?
+            .current_folder;
+        match Self::resolve(storage, source).await? {
+            RemoteItem::File(file) => {
+                storage.move_files(&[&file.file_code], &destination).await?;
+            }
+            RemoteItem::Folder(folder) => {
+                storage.move_folder(&folder, &destination).await?;
+            }
+        }
+        println!(
+            "Moved '{source}' to '{destination}'",
+            destination = destination.fld_name
+        );
+        Ok(())
+    }
+
+    /// Delete files, and folders with `recursive`
+    pub async fn rm(storage: &dyn RemoteStorage, paths: &[String], recursive: bool) -> Result<()> {
+        let mut files = vec![];
+        let mut folders = vec![];
+        for path in paths {
+            match Self::resolve(storage, path.trim()).await? {
+                RemoteItem::File(file) => files.push(file),
+                RemoteItem::Folder(_) if !recursive => {
+                    return Err(eyre!("'{path}' is a folder (use --recursive)"));
+                }
+                RemoteItem::Folder(folder) => folders.push(folder),
+            }
+        }
+
+        if !files.is_empty() {
+            let codes: Vec<&str> = files.iter().map(|f| f.file_code.as_str()).collect();
+            storage.delete_files(&codes).await?;
+        }
+        for folder in &folders {
+            storage.delete_folder(folder).await?;
+        }
+        for path in paths {
+            println!("Deleted '{}'", path.trim());
+        }
+        Ok(())
+    }
+
+    /// Rename a file or a folder
+    pub async fn rename(storage: &dyn RemoteStorage, path: &str, name: &str) -> Result<()> {
+        if name.contains('/') {
+            return Err(eyre!("The new name '{name}' cannot contain '/'"));
+        }
+        match Self::resolve(storage, path).await? {
+            RemoteItem::File(file) => storage.rename_file(&file.file_code, name).await?,
+            RemoteItem::Folder(folder) => storage.rename_folder(&folder, name).await?,
+        }
+        println!("Renamed '{path}' to '{name}'");
+        Ok(())
+    }
+
+    /// Ask for a confirmation before modifying the remote files
+    pub fn confirm(question: &str) -> Result<bool> {
+        print!("\n{question} [y/N] ");
+        io::stdout()
+            .flush()
+            .map_err(|e| eyre!("Unable to read the confirmation ({e})"))?;
+        let mut answer = String::new();
+        io::stdin()
+            .read_line(&mut answer)
+            .map_err(|e| eyre!("Unable to read the confirmation ({e})"))?;
+        Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+    }
+
+    /// Find the file or the folder at a path
+    async fn resolve(storage: &dyn RemoteStorage, path: &str) -> Result<RemoteItem> {
+        let path = Self::normalize(path);
+        let (parent, name) = path
+            .rsplit_once('/')
+            .filter(|(_, name)| !name.is_empty())
+            .ok_or_else(|| eyre!("'{path}' is the root folder"))?;
+        let parent = match parent.trim_matches('/') {
+            "" => "//",
+            _ => parent,
+        };
+
+        let listing = storage.list(parent).await?;
+        if let Some(folder) = listing
+            .folders
+            .into_iter()
+            .find(|f| Self::folder_name(f) == name)
+        {
+            return Ok(RemoteItem::Folder(folder));
+        }
+        listing
+            .files
+            .into_iter()
+            .find(|f| f.file_name == name)
+            .map(RemoteItem::File)
+            .ok_or_else(|| eyre!("'{path}' was not found on uptobox"))
+    }
+
+    /// Name of a folder, without its parents
+    pub fn folder_name(folder: &RemoteFolder) -> String {
+        match folder.name.is_empty() {
+            true => folder
+                .fld_name
+                .trim_end_matches('/')
+                .rsplit('/')
+                .next()
+                .unwrap_or_default()
+                .to_string(),
+            false => folder.name.clone(),
+        }
+    }
+
+    /// Remote paths start with '//' and have no trailing '/'
+    pub fn normalize(path: &str) -> String {
+        let path = path.trim().trim_matches('/');
+        format!("//{path}")
+    }
+
+    /// Join a folder path and a name
+    pub fn join(folder: &str, name: &str) -> String {
+        match folder.trim_matches('/') {
+            "" => format!("//{name}"),
+            _ => format!("{}/{name}", folder.trim_end_matches('/')),
+        }
+    }
+
+    /// Print the entries as a table
+    fn print_table(entries: &[RemoteEntry]) {
+        let rows: Vec<[String; 5]> = entries
+            .iter()
+            .map(|e| {
+                [
+                    e.kind.to_string(),
+                    e.size
+                        .map(|s| HumanBytes(s).to_string())
+                        .unwrap_or_default(),
+                    e.created.clone().unwrap_or_default(),
+                    e.code.clone().unwrap_or_default(),
+                    e.path.clone(),
+                ]
+            })
+            .collect();
+
+        let header = ["TYPE", "SIZE", "CREATED", "CODE", "PATH"].map(String::from);
+        let mut widths = [0; 5];
+        for row in rows.iter().chain([&header]) {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+
+        for row in [&header].into_iter().chain(rows.iter()) {
+            let line: Vec<String> = row
+                .iter()
+                .zip(widths)
+                .map(|(cell, width)| format!("{cell:<width$}"))
+                .collect();
+            println!("{}", line.join("  ").trim_end());
+        }
+    }
+}
+
+enum RemoteItem {
+    File(RemoteFile),
+    Folder(RemoteFolder),
+}
+
+#[derive(Serialize)]
+struct RemoteEntry {
+    kind: &'static str,
+    path: String,
+    size: Option<u64>,
+    created: Option<String>,
+    code: Option<String>,
+}
+
+impl RemoteEntry {
+    fn folder(path: String) -> Self {
+        Self {
+            kind: "folder",
+            path,
+            size: None,
+            created: None,
+            code: None,
+        }
+    }
+
+    fn file(folder: &str, file: &RemoteFile) -> Self {
+        Self {
+            kind: "file",
+            path: Remote::join(folder, &file.file_name),
+            size: Some(file.file_size),
+            created: Some(file.file_created.clone()),
+            code: Some(file.file_code.clone()),
+        }
+    }
+}
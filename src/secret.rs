@@ -0,0 +1,147 @@
+use argon2::Argon2;
+use chacha20poly1305::{
+    aead::{Aead, KeyInit},
+    XChaCha20Poly1305, XNonce,
+};
+use directories::ProjectDirs;
+use keyring::Entry;
+use std::{
+    env, fs,
+    io::{self, IsTerminal},
+    path::PathBuf,
+};
+
+use crate::{eyre, Result};
+
+/// Header of the encrypted secret files
+const MAGIC: &[u8] = b"downtobox-secret-v1\n";
+
+/// Keyring user under which the secrets are stored
+const KEYRING_USER: &str = "api_key";
+
+/// Environment variable holding the passphrase of the encrypted secret files
+const PASSPHRASE_ENV: &str = "DOWNTOBOX_PASSPHRASE";
+
+pub struct Secret {}
+
+/// Secrets referenced by the config instead of being written in it
+impl Secret {
+    /// Resolve `keyring:<service>` and `file:<path>` references, other values are kept as is
+    pub fn resolve(value: &str) -> Result<String> {
+        if let Some(service) = value.strip_prefix("keyring:") {
+            Entry::new(service, KEYRING_USER)
+                .and_then(|entry| entry.get_password())
+                .map_err(|e| eyre!("Unable to read '{service}' from the keyring ({e})"))
+        } else if let Some(path) = value.strip_prefix("file:") {
+            let content =
+                fs::read(path).map_err(|e| eyre!("Unable to read the secret '{path}' ({e})"))?;
+            let secret = match content.strip_prefix(MAGIC) {
+                Some(encrypted) => Self::decrypt(path, encrypted)?,
+                None => String::from_utf8(content)
+                    .map_err(|e| eyre!("Unable to read the secret '{path}' ({e})"))?,
+            };
+            Ok(secret.trim().to_string())
+        } else {
+            Ok(value.to_string())
+        }
+    }
+
+    /// Store a secret in the keyring, returning its reference
+    pub fn store_keyring(service: &str, secret: &str) -> Result<String> {
+        Entry::new(service, KEYRING_USER)
+            .and_then(|entry| entry.set_password(secret))
+            .map_err(|e| eyre!("Unable to store '{service}' in the keyring ({e})"))?;
+        Ok(format!("keyring:{service}"))
+    }
+
+    /// Store a secret in a file encrypted with a passphrase, returning its reference
+    pub fn store_file(path: &str, secret: &str) -> Result<String> {
+        let passphrase = Self::passphrase(true)?;
+        let salt: [u8; 16] = rand::random();
+        let nonce: [u8; 24] = rand::random();
+        let encrypted = Self::cipher(&passphrase, &salt)?
+            .encrypt(XNonce::from_slice(&nonce), secret.as_bytes())
+            .map_err(|e| eyre!("Unable to encrypt the secret ({e})"))?;
+
+        let content = [MAGIC, &salt, &nonce, &encrypted].concat();
+        if let Some(dir) = PathBuf::from(path).parent() {
+            fs::create_dir_all(dir)
+                .map_err(|e| eyre!("Unable to write the secret '{path}' ({e})"))?;
+        }
+        fs::write(path, content).map_err(|e| eyre!("Unable to write the secret '{path}' ({e})"))?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(path, fs::Permissions::from_mode(0o600))
+                .map_err(|e| eyre!("Unable to write the secret '{path}' ({e})"))?;
+        }
+        Ok(format!("file:{path}"))
+    }
+
+    /// Default path of the encrypted secret file
+    pub fn default_file() -> Result<String> {
+        let dir = ProjectDirs::from("dev", "latruiterouge", "downtobox")
+            .ok_or_else(|| eyre!("The data path cannot be determined"))?;
+        Ok(dir.data_dir().join("api_key.secret").display().to_string())
+    }
+
+    /// Read a secret without echoing it on a terminal
+    pub fn read(prompt: &str) -> Result<String> {
+        let secret = match io::stdin().is_terminal() {
+            true => rpassword::prompt_password(prompt)
+                .map_err(|e| eyre!("Unable to read the secret ({e})"))?,
+            false => {
+                let mut secret = String::new();
+                io::stdin()
+                    .read_line(&mut secret)
+                    .map_err(|e| eyre!("Unable to read the secret ({e})"))?;
+                secret
+            }
+        };
+        match secret.trim() {
+            "" => Err(eyre!("The secret is empty")),
+            secret => Ok(secret.to_string()),
+        }
+    }
+
+    /// Decrypt the salt, nonce and ciphertext following the header of a secret file
+    fn decrypt(path: &str, content: &[u8]) -> Result<String> {
+        if content.len() < 40 {
+            return Err(eyre!(
+                "Unable to decrypt the secret '{path}' (truncated file)"
+            ));
+        }
+        let (salt, content) = content.split_at(16);
+        let (nonce, encrypted) = content.split_at(24);
+        let secret = Self::cipher(&Self::passphrase(false)?, salt)?
+            .decrypt(XNonce::from_slice(nonce), encrypted)
+            .map_err(|_| eyre!("Unable to decrypt the secret '{path}' (wrong passphrase)"))?;
+        String::from_utf8(secret).map_err(|e| eyre!("Unable to decrypt the secret '{path}' ({e})"))
+    }
+
+    /// Cipher of a passphrase, the key is derived with Argon2
+    fn cipher(passphrase: &str, salt: &[u8]) -> Result<XChaCha20Poly1305> {
+        let mut key = [0u8; 32];
+        Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+            .map_err(|e| eyre!("Unable to derive the key of the secret ({e})"))?;
+        Ok(XChaCha20Poly1305::new(&key.into()))
+    }
+
+    /// Passphrase of the encrypted files, from the environment for headless use
+    fn passphrase(confirm: bool) -> Result<String> {
+        if let Ok(passphrase) = env::var(PASSPHRASE_ENV) {
+            return Ok(passphrase);
+        }
+        let passphrase = rpassword::prompt_password("Passphrase of the secret file: ")
+            .map_err(|e| eyre!("Unable to read the passphrase (set {PASSPHRASE_ENV}) ({e})"))?;
+        if confirm
+            && rpassword::prompt_password("Confirm the passphrase: ")
+                .map_err(|e| eyre!("Unable to read the passphrase ({e})"))?
+                != passphrase
+        {
+            return Err(eyre!("The passphrases do not match"));
+        }
+        Ok(passphrase)
+    }
+}
@@ -0,0 +1,381 @@
+use axum::{extract::Request, Router};
+use bytes::Bytes;
+use chrono::{Local, NaiveDateTime, TimeZone};
+use dav_server::{
+    davpath::DavPath,
+    fs::{
+        DavDirEntry, DavFile, DavFileSystem, DavMetaData, FsError, FsFuture, FsResult, FsStream,
+        OpenOptions, ReadDirMeta,
+    },
+    DavHandler, DavMethodSet,
+};
+use futures_util::{stream, FutureExt};
+use reqwest::{header::RANGE, Client, Response, StatusCode};
+use std::{
+    collections::HashMap,
+    io::SeekFrom,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tokio::net::TcpListener;
+
+use crate::{
+    eyre,
+    remote::Remote,
+    storage::{RemoteFile, RemoteListing, RemoteStorage},
+    Result,
+};
+
+/// Time during which a listing is reused
+const LISTING_TTL: Duration = Duration::from_secs(60);
+
+/// Listings of the remote folders with the time they were fetched
+type Listings = Arc<Mutex<HashMap<String, (Instant, Arc<RemoteListing>)>>>;
+
+pub struct Serve {}
+
+/// Read-only shares of the remote storage
+impl Serve {
+    /// Serve a remote folder over WebDAV, proxying or redirecting the downloads
+    pub async fn webdav(
+        storage: Arc<dyn RemoteStorage>,
+        client: Client,
+        address: &str,
+        root: &str,
+        redirect: bool,
+    ) -> Result<()> {
+        // A redirect would send the clients to links they cannot open without the credentials
+        if redirect && storage.private_downloads() {
+            return Err(eyre!(
+                "Unable to redirect the downloads, the storage needs credentials (serve without --redirect)"
+            ));
+        }
+
+        let fs = RemoteFs {
+            storage,
+            client,
+            root: Remote::normalize(root),
+            listings: Arc::new(Mutex::new(HashMap::new())),
+        };
+        let dav = DavHandler::builder()
+            .filesystem(Box::new(fs))
+            .methods(DavMethodSet::WEBDAV_RO)
+            .redirect(redirect)
+            .autoindex(true)
+            .build_handler();
+        let app = Router::new().fallback(move |req: Request| {
+            let dav = dav.clone();
+            async move { dav.handle(req).await }
+        });
+
+        let listener = TcpListener::bind(address)
+            .await
+            .map_err(|e| eyre!("Unable to listen on '{address}' ({e})"))?;
+        println!("Serving '{root}' on http://{address}");
+        axum::serve(listener, app)
+            .await
+            .map_err(|e| eyre!("Unable to serve '{root}' ({e})"))
+    }
+}
+
+/// Remote folder seen as a filesystem
+#[derive(Clone)]
+struct RemoteFs {
+    storage: Arc<dyn RemoteStorage>,
+    client: Client,
+    root: String,
+    listings: Listings,
+}
+
+impl RemoteFs {
+    /// Remote path of a path of the share
+    fn path(&self, path: &DavPath) -> String {
+        let path = String::from_utf8_lossy(path.as_bytes());
+        Remote::join(&self.root, path.trim_matches('/'))
+            .trim_end_matches('/')
+            .to_string()
+    }
+
+    /// List a folder, reusing the recent listings
+    async fn listing(&self, path: &str) -> FsResult<Arc<RemoteListing>> {
+        if let Ok(listings) = self.listings.lock() {
+            if let Some((date, listing)) = listings.get(path) {
+                if date.elapsed() < LISTING_TTL {
+                    return Ok(listing.clone());
+                }
+            }
+        }
+
+        let listing = Arc::new(
+            self.storage
+                .list(path)
+                .await
+                .map_err(|_| FsError::NotFound)?,
+        );
+        if let Ok(mut listings) = self.listings.lock() {
+            listings.insert(path.to_string(), (Instant::now(), listing.clone()));
+        }
+        Ok(listing)
+    }
+
+    /// Find a file or a folder from the listing of its parent
+    async fn find(&self, path: &str) -> FsResult<(RemoteMeta, Option<RemoteFile>)> {
+        if path.trim_matches('/').is_empty() || path == self.root {
+            return Ok((RemoteMeta::folder(), None));
+        }
+        let (parent, name) = path.rsplit_once('/').ok_or(FsError::NotFound)?;
+        let listing = self.listing(&Remote::normalize(parent)).await?;
+
+        if listing
+            .folders
+            .iter()
+            .any(|f| Remote::folder_name(f) == name)
+        {
+            return Ok((RemoteMeta::folder(), None));
+        }
+        let file = listing
+            .files
+            .iter()
+            .find(|f| f.file_name == name)
+            .ok_or(FsError::NotFound)?;
+        Ok((RemoteMeta::file(file), Some(file.clone())))
+    }
+}
+
+impl DavFileSystem for RemoteFs {
+    fn open<'a>(
+        &'a self,
+        path: &'a DavPath,
+        options: OpenOptions,
+    ) -> FsFuture<'a, Box<dyn DavFile>> {
+        async move {
+            if options.write || options.append || options.create || options.create_new {
+                return Err(FsError::Forbidden);
+            }
+            match self.find(&self.path(path)).await? {
+                (meta, Some(file)) => Ok(Box::new(RemoteReader {
+                    storage: self.storage.clone(),
+                    client: self.client.clone(),
+                    file_code: file.file_code,
+                    meta,
+                    url: None,
+                    position: 0,
+                    response: None,
+                    buffer: Bytes::new(),
+                }) as Box<dyn DavFile>),
+                (_, None) => Err(FsError::Forbidden),
+            }
+        }
+        .boxed()
+    }
+
+    fn read_dir<'a>(
+        &'a self,
+        path: &'a DavPath,
+        _meta: ReadDirMeta,
+    ) -> FsFuture<'a, FsStream<Box<dyn DavDirEntry>>> {
+        async move {
+            let listing = self.listing(&Remote::normalize(&self.path(path))).await?;
+            let mut entries: Vec<FsResult<Box<dyn DavDirEntry>>> = vec![];
+            for folder in &listing.folders {
+                entries.push(Ok(Box::new(RemoteEntry {
+                    name: Remote::folder_name(folder),
+                    meta: RemoteMeta::folder(),
+                })));
+            }
+            for file in &listing.files {
+                entries.push(Ok(Box::new(RemoteEntry {
+                    name: file.file_name.clone(),
+                    meta: RemoteMeta::file(file),
+                })));
+            }
+            Ok(Box::pin(stream::iter(entries)) as FsStream<Box<dyn DavDirEntry>>)
+        }
+        .boxed()
+    }
+
+    fn metadata<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, Box<dyn DavMetaData>> {
+        async move {
+            let (meta, _) = self.find(&self.path(path)).await?;
+            Ok(Box::new(meta) as Box<dyn DavMetaData>)
+        }
+        .boxed()
+    }
+}
+
+/// Size and date of a remote file or folder
+#[derive(Debug, Clone)]
+struct RemoteMeta {
+    size: u64,
+    modified: SystemTime,
+    is_dir: bool,
+}
+
+impl RemoteMeta {
+    fn folder() -> Self {
+        Self {
+            size: 0,
+            modified: UNIX_EPOCH,
+            is_dir: true,
+        }
+    }
+
+    fn file(file: &RemoteFile) -> Self {
+        let modified = NaiveDateTime::parse_from_str(&file.file_created, "%Y-%m-%d %H:%M:%S")
+            .ok()
+            .and_then(|date| Local.from_local_datetime(&date).single())
+            .map(SystemTime::from)
+            .unwrap_or(UNIX_EPOCH);
+        Self {
+            size: file.file_size,
+            modified,
+            is_dir: false,
+        }
+    }
+}
+
+impl DavMetaData for RemoteMeta {
+    fn len(&self) -> u64 {
+        self.size
+    }
+
+    fn modified(&self) -> FsResult<SystemTime> {
+        Ok(self.modified)
+    }
+
+    fn is_dir(&self) -> bool {
+        self.is_dir
+    }
+}
+
+/// Entry of a remote folder
+struct RemoteEntry {
+    name: String,
+    meta: RemoteMeta,
+}
+
+impl DavDirEntry for RemoteEntry {
+    fn name(&self) -> Vec<u8> {
+        self.name.as_bytes().to_vec()
+    }
+
+    fn metadata(&self) -> FsFuture<'_, Box<dyn DavMetaData>> {
+        let meta = self.meta.clone();
+        async move { Ok(Box::new(meta) as Box<dyn DavMetaData>) }.boxed()
+    }
+}
+
+/// Remote file read from its download link, one range request per seek
+struct RemoteReader {
+    storage: Arc<dyn RemoteStorage>,
+    client: Client,
+    file_code: String,
+    meta: RemoteMeta,
+    url: Option<String>,
+    position: u64,
+    response: Option<Response>,
+    buffer: Bytes,
+}
+
+impl std::fmt::Debug for RemoteReader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RemoteReader")
+            .field("file_code", &self.file_code)
+            .field("position", &self.position)
+            .finish()
+    }
+}
+
+impl RemoteReader {
+    /// Download link of the file, resolved once
+    async fn url(&mut self) -> FsResult<String> {
+        if self.url.is_none() {
+            let url = self
+                .storage
+                .get_download_url(&self.file_code)
+                .await
+                .map_err(|_| FsError::Forbidden)?;
+            self.url = Some(url);
+        }
+        self.url.clone().ok_or(FsError::GeneralFailure)
+    }
+}
+
+impl DavFile for RemoteReader {
+    fn metadata(&mut self) -> FsFuture<'_, Box<dyn DavMetaData>> {
+        let meta = self.meta.clone();
+        async move { Ok(Box::new(meta) as Box<dyn DavMetaData>) }.boxed()
+    }
+
+    fn write_buf(&mut self, _buf: Box<dyn bytes::Buf + Send>) -> FsFuture<'_, ()> {
+        async { Err(FsError::Forbidden) }.boxed()
+    }
+
+    fn write_bytes(&mut self, _buf: Bytes) -> FsFuture<'_, ()> {
+        async { Err(FsError::Forbidden) }.boxed()
+    }
+
+    fn read_bytes(&mut self, count: usize) -> FsFuture<'_, Bytes> {
+        async move {
+            if self.position >= self.meta.size {
+                return Ok(Bytes::new());
+            }
+
+            // Request the rest of the file from the current position
+            if self.response.is_none() {
+                let url = self.url().await?;
+                let res = self
+                    .storage
+                    .download(&self.client, &url)
+                    .header(RANGE, format!("bytes={}-", self.position))
+                    .send()
+                    .await
+                    .map_err(|_| FsError::GeneralFailure)?;
+                match res.status() {
+                    StatusCode::PARTIAL_CONTENT => (),
+                    StatusCode::OK if self.position == 0 => (),
+                    _ => return Err(FsError::GeneralFailure),
+                }
+                self.response = Some(res);
+            }
+
+            if self.buffer.is_empty() {
+                let response = self.response.as_mut().ok_or(FsError::GeneralFailure)?;
+                self.buffer = response
+                    .chunk()
+                    .await
+                    .map_err(|_| FsError::GeneralFailure)?
+                    .unwrap_or_default();
+            }
+            let bytes = self.buffer.split_to(count.min(self.buffer.len()));
+            self.position += bytes.len() as u64;
+            Ok(bytes)
+        }
+        .boxed()
+    }
+
+    fn seek(&mut self, position: SeekFrom) -> FsFuture<'_, u64> {
+        async move {
+            let position = match position {
+                SeekFrom::Start(offset) => offset,
+                SeekFrom::End(offset) => self.meta.size.saturating_add_signed(offset),
+                SeekFrom::Current(offset) => self.position.saturating_add_signed(offset),
+            };
+            if position != self.position {
+                self.position = position;
+                self.response = None;
+                self.buffer = Bytes::new();
+            }
+            Ok(position)
+        }
+        .boxed()
+    }
+
+    fn flush(&mut self) -> FsFuture<'_, ()> {
+        async { Ok(()) }.boxed()
+    }
+
+    fn redirect_url(&mut self) -> FsFuture<'_, Option<String>> {
+        async move { Ok(Some(self.url().await?)) }.boxed()
+    }
+}
@@ -0,0 +1,249 @@
+use reqwest::Client;
+use std::{
+    fs,
+    io::{self, Write},
+    path::Path,
+};
+use toml::{Table, Value};
+
+use crate::{
+    account::Account,
+    config::{Config, LocalPolicy, Storage, DEFAULT_API_KEY},
+    eyre,
+    secret::Secret,
+    storage::{self, RemoteStorage},
+    Limiters, Report, Result, UptoboxApi,
+};
+
+pub struct Setup {}
+
+/// First run setup and validation of the config file
+impl Setup {
+    /// Ask for the settings needed by every command and write them to the config file
+    pub async fn init(
+        client: &Client,
+        path: Option<&str>,
+        overrides: &[(&str, String)],
+    ) -> Result<()> {
+        let toml = Config::path(path)?;
+        let mut table = match Path::new(&toml).exists() {
+            true => Config::read(Some(&toml))?,
+            false => Table::try_from(Config::default())
+                .map_err(|e| eyre!("Unable to create the config file ({e})"))?,
+        };
+        let mut config =
+            Config::load_unresolved(table.clone(), None, overrides).unwrap_or_default();
+
+        // The key is checked against the account endpoint, it is written as given so that the
+        // references to the keyring or to a file are kept
+        let mut api_key = None;
+        if let Storage::Uptobox = config.storage {
+            let current = match table.get("api_key").and_then(Value::as_str) {
+                None | Some(DEFAULT_API_KEY) => "",
+                Some(key) => key,
+            };
+            loop {
+                let key = Self::ask("Uptobox API key", current)?;
+                let resolved = match Secret::resolve(&key) {
+                    Ok(resolved) => resolved,
+                    Err(e) => {
+                        eprintln!("{e}");
+                        continue;
+                    }
+                };
+                let api = UptoboxApi::new(client.clone(), &resolved, &config.api_url);
+                match api.get_account().await {
+                    Ok(account) => {
+                        if !account.premium {
+                            eprintln!("Warning: a premium account is needed to use this software");
+                        }
+                        println!("Logged in as '{}'", account.login);
+                        config.api_key = resolved;
+                        api_key = Some(key);
+                        break;
+                    }
+                    Err(e) => eprintln!("Unable to retreive your uptobox account ({e})"),
+                }
+            }
+        }
+
+        loop {
+            // The answer is written as given, the config expands '~/' when it is loaded
+            let local = Self::ask("Local download directory", &config.local_path)?;
+            match Config::expand_home(&local).and_then(|path| Self::check_local(&path)) {
+                Ok(_) => {
+                    config.local_path = local;
+                    break;
+                }
+                Err(e) => eprintln!("{e}"),
+            }
+        }
+
+        let mut config = config.resolve()?;
+        let remote = storage::connect(client.clone(), &config);
+        loop {
+            let destination = Self::ask("Remote destination folder", &config.destination_path)?;
+            match Self::check_destination(remote.as_ref(), &destination).await {
+                Ok(_) => {
+                    config.destination_path = destination;
+                    break;
+                }
+                Err(e) => eprintln!("{e}"),
+            }
+        }
+
+        if let Some(api_key) = api_key {
+            table.insert(String::from("api_key"), Value::String(api_key));
+        }
+        table.insert(String::from("local_path"), Value::String(config.local_path));
+        table.insert(
+            String::from("destination_path"),
+            Value::String(config.destination_path),
+        );
+        let content =
+            toml::to_string(&table).map_err(|e| eyre!("Unable to write the config file ({e})"))?;
+        Config::write(&toml, &content)?;
+        println!("Config written to '{toml}'");
+        Ok(())
+    }
+
+    /// Store the API key in the keyring, or in an encrypted file, and reference it in the config file
+    pub fn set_key(path: Option<&str>, service: &str, file: Option<&str>) -> Result<()> {
+        let key = Secret::read("Uptobox API key: ")?;
+        let reference = match file {
+            Some(file) => Secret::store_file(file, &key)?,
+            None => match Secret::store_keyring(service, &key) {
+                Ok(reference) => reference,
+                Err(e) => {
+                    eprintln!("Warning: {e}, the key is stored in an encrypted file instead");
+                    Secret::store_file(&Secret::default_file()?, &key)?
+                }
+            },
+        };
+
+        let toml = Config::path(path)?;
+        let mut table = Config::read(path)?;
+        table.insert(String::from("api_key"), Value::String(reference.clone()));
+        let content =
+            toml::to_string(&table).map_err(|e| eyre!("Unable to write the config file ({e})"))?;
+        Config::write(&toml, &content)?;
+        println!("API key stored as '{reference}'");
+        Ok(())
+    }
+
+    /// Validate a config file, reporting every problem found
+    pub async fn check(
+        client: &Client,
+        path: Option<&str>,
+        profile: Option<&str>,
+        overrides: &[(&str, String)],
+    ) -> Result<()> {
+        let mut problems = vec![];
+        let table = match Config::read(path) {
+            Ok(table) => table,
+            Err(e) => return Self::report(vec![e]),
+        };
+
+        // The other settings are still checked without an unknown profile
+        let config = match Config::load_unresolved(table.clone(), profile, overrides) {
+            Ok(config) => config,
+            Err(e) => {
+                problems.push(e);
+                match Config::load_unresolved(table, None, overrides) {
+                    Ok(config) => config,
+                    Err(_) => return Self::report(problems),
+                }
+            }
+        };
+
+        if let Err(e) = Self::check_local(&config.local_path) {
+            problems.push(e);
+        }
+        if let LocalPolicy::Archive { path } = &config.local_policy {
+            if let Err(e) = Self::check_local(path) {
+                problems.push(e);
+            }
+        }
+        if let Err(e) = Limiters::new(&config.limits, &None, &None) {
+            problems.push(e);
+        }
+
+        // The remote checks need the secrets and a working account
+        if let Err(e) = Self::check_remote(client, config).await {
+            problems.push(e);
+        }
+
+        Self::report(problems)
+    }
+
+    /// Check the account and the destination of the remote storage
+    async fn check_remote(client: &Client, config: Config) -> Result<()> {
+        let config = config.resolve()?;
+        let remote = storage::connect(client.clone(), &config);
+        match (&config.storage, config.api_key.as_str()) {
+            (Storage::Uptobox, "" | DEFAULT_API_KEY) => Err(eyre!(
+                "The API key is not set (run 'downtobox config init')"
+            )),
+            _ => Account::check(remote.as_ref(), config.premium_warning).await,
+        }?;
+        Self::check_destination(remote.as_ref(), &config.destination_path).await
+    }
+
+    /// Print the problems found in a config
+    fn report(problems: Vec<Report>) -> Result<()> {
+        if problems.is_empty() {
+            println!("The config is valid");
+            return Ok(());
+        }
+        for problem in &problems {
+            println!("- {problem}");
+        }
+        Err(eyre!("{} problem(s) found in the config", problems.len()))
+    }
+
+    /// Check if files can be written to a local directory, or to its future parent
+    fn check_local(dir: &str) -> Result<()> {
+        let existing = Path::new(dir)
+            .ancestors()
+            .find(|p| p.exists())
+            .ok_or_else(|| eyre!("Unable to find the directory '{dir}'"))?;
+        if !existing.is_dir() {
+            return Err(eyre!("'{}' is not a directory", existing.display()));
+        }
+
+        let probe = existing.join(".downtobox");
+        fs::write(&probe, b"")
+            .and_then(|_| fs::remove_file(&probe))
+            .map_err(|e| eyre!("Unable to write to '{dir}' ({e})"))
+    }
+
+    /// Check if a remote folder exists
+    async fn check_destination(storage: &dyn RemoteStorage, path: &str) -> Result<()> {
+        storage.list(path).await.map(|_| ()).map_err(|e| {
+            eyre!("The destination '{path}' does not exist on the remote storage ({e})")
+        })
+    }
+
+    /// Ask for a setting, an empty answer keeps the current value
+    fn ask(question: &str, current: &str) -> Result<String> {
+        match current.is_empty() {
+            true => print!("{question}: "),
+            false => print!("{question} [{current}]: "),
+        }
+        io::stdout()
+            .flush()
+            .map_err(|e| eyre!("Unable to read the answer ({e})"))?;
+        let mut answer = String::new();
+        let read = io::stdin()
+            .read_line(&mut answer)
+            .map_err(|e| eyre!("Unable to read the answer ({e})"))?;
+        if read == 0 {
+            return Err(eyre!("The setup was interrupted"));
+        }
+        match answer.trim() {
+            "" if !current.is_empty() => Ok(current.to_string()),
+            "" => Self::ask(question, current),
+            answer => Ok(answer.to_string()),
+        }
+    }
+}
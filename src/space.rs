@@ -0,0 +1,41 @@
+use indicatif::HumanBytes;
+use std::path::Path;
+
+use crate::{eyre, Result};
+
+pub struct Space {}
+
+/// Free disk space of the local directory
+impl Space {
+    /// Check that `size` bytes plus a margin in MiB fit in the directory
+    pub fn check(dir: &str, size: u64, margin: u64) -> Result<()> {
+        let available = Self::available(dir)?;
+
+        let needed = size + margin * 1024 * 1024;
+        if needed > available {
+            Err(eyre!(
+                "Not enough free space in '{dir}' ({} needed, {} available)",
+                HumanBytes(needed),
+                HumanBytes(available)
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Bytes that can be written to the directory once the margin in MiB is kept
+    pub fn free(dir: &str, margin: u64) -> Result<u64> {
+        Ok(Self::available(dir)?.saturating_sub(margin * 1024 * 1024))
+    }
+
+    /// Free space of the filesystem holding the directory
+    fn available(dir: &str) -> Result<u64> {
+        // The directory may not be created yet
+        let existing = Path::new(dir)
+            .ancestors()
+            .find(|p| p.exists())
+            .ok_or_else(|| eyre!("Unable to find the filesystem of '{dir}'"))?;
+        fs2::available_space(existing)
+            .map_err(|e| eyre!("Unable to retreive the free space of '{dir}' ({e})"))
+    }
+}
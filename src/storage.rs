@@ -0,0 +1,143 @@
+use async_trait::async_trait;
+use reqwest::{Body, Client, RequestBuilder};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use std::sync::Arc;
+
+use crate::{
+    api::UptoboxApi,
+    config::{Config, Storage},
+    webdav::WebdavStorage,
+    Result,
+};
+
+/// Hoster the files are backed up to
+#[async_trait]
+pub trait RemoteStorage: Send + Sync {
+    /// Parse the file code from a link
+    fn file_code(&self, link: &str) -> Result<String>;
+
+    /// Get the name and size of a file
+    async fn get_file(&self, file_code: &str) -> Result<RemoteFile>;
+
+    /// Get the direct download link of a file, without any credentials in it
+    async fn get_download_url(&self, file_code: &str) -> Result<String>;
+
+    /// Request of a download link, authenticated when the storage needs it
+    fn download(&self, client: &Client, url: &str) -> RequestBuilder {
+        client.get(url)
+    }
+
+    /// Check if the download links only work with the credentials of the storage
+    fn private_downloads(&self) -> bool {
+        false
+    }
+
+    /// Upload a file to the root folder
+    async fn upload(&self, body: Body, name: &str) -> Result<()>;
+
+    /// List a remote folder
+    async fn list(&self, path: &str) -> Result<RemoteListing>;
+
+    /// Create a folder
+    async fn create_folder(&self, path: &str, name: &str) -> Result<()>;
+
+    /// Move files into a folder
+    async fn move_files(&self, file_codes: &[&str], destination: &RemoteFolder) -> Result<()>;
+
+    /// Move a folder into another folder
+    async fn move_folder(&self, folder: &RemoteFolder, destination: &RemoteFolder) -> Result<()>;
+
+    /// Rename a file
+    async fn rename_file(&self, file_code: &str, name: &str) -> Result<()>;
+
+    /// Rename a folder
+    async fn rename_folder(&self, folder: &RemoteFolder, name: &str) -> Result<()>;
+
+    /// Delete files
+    async fn delete_files(&self, file_codes: &[&str]) -> Result<()>;
+
+    /// Delete a folder and its content
+    async fn delete_folder(&self, folder: &RemoteFolder) -> Result<()>;
+
+    /// Get the account
+    async fn get_account(&self) -> Result<RemoteAccount>;
+}
+
+/// Connect to the storage selected in the configuration
+pub fn connect(client: Client, config: &Config) -> Arc<dyn RemoteStorage> {
+    match &config.storage {
+        Storage::Uptobox => Arc::new(UptoboxApi::new(client, &config.api_key, &config.api_url)),
+        Storage::Webdav {
+            url,
+            username,
+            password,
+        } => Arc::new(WebdavStorage::new(client, url, username, password)),
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RemoteListing {
+    #[serde(rename = "currentFolder")]
+    pub current_folder: RemoteFolder,
+    #[serde(default)]
+    pub folders: Vec<RemoteFolder>,
+    #[serde(default)]
+    pub files: Vec<RemoteFile>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct RemoteFolder {
+    pub fld_id: usize,
+    /// Full path of the folder
+    pub fld_name: String,
+    #[serde(default)]
+    pub name: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct RemoteFile {
+    pub file_code: String,
+    pub file_name: String,
+    #[serde(default)]
+    pub file_size: u64,
+    #[serde(default)]
+    pub file_created: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RemoteAccount {
+    pub login: String,
+    #[serde(default)]
+    pub email: String,
+    #[serde(default, deserialize_with = "deserialize_text")]
+    pub point: String,
+    #[serde(default)]
+    pub premium_expire: String,
+    #[serde(deserialize_with = "deserialize_flag")]
+    pub premium: bool,
+}
+
+/// Flags are sent as booleans or as 0 and 1
+fn deserialize_flag<'de, D>(deserializer: D) -> Result<bool, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Value::deserialize(deserializer)? {
+        Value::Bool(flag) => Ok(flag),
+        Value::Number(flag) => Ok(flag.as_u64() != Some(0)),
+        _ => Ok(false),
+    }
+}
+
+/// Numbers may be sent as strings
+fn deserialize_text<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Value::deserialize(deserializer)? {
+        Value::String(text) => Ok(text),
+        Value::Null => Ok(String::new()),
+        value => Ok(value.to_string()),
+    }
+}
@@ -1,80 +1,273 @@
+use deunicode::deunicode_char;
 use itertools::Itertools;
+use rand::{distributions::Alphanumeric, Rng};
 use regex::Regex;
+use std::{collections::BTreeMap, fs::File, io};
 
 use crate::{
+    config::{Aliases, Config, Suffix, SuffixMode},
     eyre,
-    matroska::{Matroska, MatroskaLang, MatroskaSource},
+    matroska::{Matroska, MatroskaEdition, MatroskaLang, MatroskaService, MatroskaSource},
     Result,
 };
 
+/// Length of the random and hash suffixes
+const SUFFIX_LENGTH: usize = 7;
+
 pub struct Title {}
 
 impl Title {
     /// Generate the file name
     pub fn generate_film_title(
+        config: &Config,
         path: &str,
+        file_name: &str,
         title: &str,
-        langs: &str,
-        source: &str,
+        (langs, source, services, editions, group): (&str, &str, &str, &str, Option<&str>),
     ) -> Result<String> {
+        let aliases = &config.aliases;
         let file = Matroska::new(path)?;
-        let title = title.trim().replace(' ', ".");
-        let langs = Self::get_langs(langs)?;
-        let srouce = Self::get_source(source);
+        let title = Self::sanitize(title)?;
+        let editions = Self::get_editions(aliases, editions, file_name)?;
+        let langs = Self::get_langs(aliases, langs)?;
+        let services = Self::get_services(aliases, services, file_name)?;
+        let srouce = Self::get_source(aliases, source)?;
         let resolution = file.get_resolution()?;
         let audio_codecs = file.get_audio_codecs()?;
         let video_codecs = file.get_video_codecs()?;
+        let group = Self::get_group(aliases, group, file_name)?;
 
-        let output = [
-            title,
+        let suffix = [
+            editions,
             langs,
             resolution,
+            services,
             srouce,
             audio_codecs,
-            video_codecs,
+            [video_codecs, group]
+                .iter()
+                .filter(|t| !t.is_empty())
+                .join("-"),
             String::from("mkv"),
         ]
         .iter()
         .filter(|t| !t.is_empty())
         .join(".");
 
-        Ok(output)
+        // The file names may take the suffix after the title is generated
+        let reserved = Self::suffix_length(&config.suffix)?;
+        Self::fit(&title, &suffix, config.max_name_length, reserved)
     }
 
     // (?:S|s)[0-9]{1,3}(?:E|e)[0-9]{1,3}|(?:E|e)[0-9]{1,3}
 
     /// Generate the show name
     pub fn generate_show_title(
+        config: &Config,
         path: &str,
         file_name: &str,
         title: &str,
-        langs: &str,
-        source: &str,
+        (langs, source, services, editions, group): (&str, &str, &str, &str, Option<&str>),
     ) -> Result<String> {
+        let aliases = &config.aliases;
         let file = Matroska::new(path)?;
-        let title = title.trim().replace(' ', ".");
-        let langs = Self::get_langs(langs)?;
+        let title = Self::sanitize(title)?;
+        let editions = Self::get_editions(aliases, editions, file_name)?;
+        let langs = Self::get_langs(aliases, langs)?;
         let episode = Self::get_episode(file_name)?;
-        let srouce = Self::get_source(source);
+        let services = Self::get_services(aliases, services, file_name)?;
+        let srouce = Self::get_source(aliases, source)?;
         let resolution = file.get_resolution()?;
         let audio_codecs = file.get_audio_codecs()?;
         let video_codecs = file.get_video_codecs()?;
+        let group = Self::get_group(aliases, group, file_name)?;
 
-        let output = [
-            title,
+        let suffix = [
             episode,
+            editions,
             langs,
             resolution,
+            services,
             srouce,
             audio_codecs,
-            video_codecs,
+            [video_codecs, group]
+                .iter()
+                .filter(|t| !t.is_empty())
+                .join("-"),
             String::from("mkv"),
         ]
         .iter()
         .filter(|t| !t.is_empty())
         .join(".");
 
-        Ok(output)
+        // The file names may take the suffix after the title is generated
+        let reserved = Self::suffix_length(&config.suffix)?;
+        Self::fit(&title, &suffix, config.max_name_length, reserved)
+    }
+
+    /// Transliterate the accented letters, drop the reserved characters and collapse the separators
+    pub fn sanitize(title: &str) -> Result<String> {
+        // The transliterations go through the same filter, '½' gives '1/2'
+        let allowed = |c: char| match c {
+            c if c.is_ascii_alphanumeric()
+                || matches!(c, '.' | '-' | '(' | ')' | '&' | '+' | '!' | ',') =>
+            {
+                Some(c)
+            }
+            c if c.is_whitespace() || c == '_' => Some('.'),
+            _ => None,
+        };
+        let title: String = title
+            .chars()
+            .map(|c| match allowed(c) {
+                Some(c) => c.to_string(),
+                None if c.is_alphanumeric() => deunicode_char(c)
+                    .unwrap_or_default()
+                    .trim()
+                    .chars()
+                    .filter_map(allowed)
+                    .collect(),
+                None => String::new(),
+            })
+            .collect();
+
+        // Dashes absorb the dots around them
+        let separators = Regex::new(r"\.*-[.-]*")?;
+        let title = separators.replace_all(&title, "-");
+        let dots = Regex::new(r"\.{2,}")?;
+        Ok(dots
+            .replace_all(&title, ".")
+            .trim_matches(['.', '-'])
+            .to_string())
+    }
+
+    /// Join the title and the technical suffix, truncating the title to the maximum length
+    /// less the reserved characters
+    fn fit(title: &str, suffix: &str, max_length: usize, reserved: usize) -> Result<String> {
+        if title.is_empty() {
+            return Err(eyre!("The title is empty once sanitised"));
+        }
+        let name = format!("{title}.{suffix}");
+        if max_length == 0 || name.len() + reserved <= max_length {
+            return Ok(name);
+        }
+
+        let available = max_length.saturating_sub(suffix.len() + 1 + reserved);
+        let title = title[..available.min(title.len())].trim_end_matches(['.', '-']);
+        match title.is_empty() {
+            true => Err(eyre!(
+                "Unable to fit '{name}' in {max_length} characters (max_name_length)"
+            )),
+            false => Ok(format!("{title}.{suffix}")),
+        }
+    }
+
+    /// Characters the suffix adds to the file names
+    fn suffix_length(suffix: &Suffix) -> Result<usize> {
+        if !suffix.file_name {
+            return Ok(0);
+        }
+        Ok(match suffix.mode {
+            SuffixMode::None => 0,
+            SuffixMode::Random | SuffixMode::Hash => SUFFIX_LENGTH + 1,
+            SuffixMode::Tag => Self::sanitize(&suffix.tag)?.len() + 1,
+        })
+    }
+
+    /// Suffix of the title of a file, the hash is computed before the title is updated
+    pub fn suffix(suffix: &Suffix, path: &str) -> Result<Option<String>> {
+        match suffix.mode {
+            SuffixMode::None => Ok(None),
+            SuffixMode::Random => Ok(Some(
+                rand::thread_rng()
+                    .sample_iter(&Alphanumeric)
+                    .take(SUFFIX_LENGTH)
+                    .map(char::from)
+                    .collect(),
+            )),
+            SuffixMode::Hash => {
+                let mut file =
+                    File::open(path).map_err(|e| eyre!("Unable to hash '{path}' ({e})"))?;
+                let mut hasher = blake3::Hasher::new();
+                io::copy(&mut file, &mut hasher)
+                    .map_err(|e| eyre!("Unable to hash '{path}' ({e})"))?;
+                Ok(Some(
+                    hasher.finalize().to_hex()[..SUFFIX_LENGTH].to_string(),
+                ))
+            }
+            SuffixMode::Tag => match Self::sanitize(&suffix.tag)?.as_str() {
+                "" => Err(eyre!("The suffix tag is empty (suffix.tag)")),
+                tag => Ok(Some(tag.to_string())),
+            },
+        }
+    }
+
+    /// File name with the suffix before its extension
+    pub fn suffixed_name(name: &str, suffix: &str) -> String {
+        format!("{}.{suffix}.mkv", name.trim_end_matches(".mkv"))
+    }
+
+    /// Check if a file name is a title, followed by a suffix when the file names have one
+    pub fn same_name(suffix: &Suffix, name: &str, title: &str) -> bool {
+        name == title
+            || Self::strip_suffix(suffix, name)
+                .is_some_and(|stem| stem == title.trim_end_matches(".mkv"))
+    }
+
+    /// File name without its extension and the suffix of the file names, when it has one
+    ///
+    /// The suffix is only taken where the uploads write it, after the video codecs and the group.
+    pub fn strip_suffix<'a>(suffix: &Suffix, name: &'a str) -> Option<&'a str> {
+        if !suffix.file_name {
+            return None;
+        }
+        let name = name.trim_end_matches(".mkv");
+        let stem = match suffix.mode {
+            SuffixMode::None => None,
+            SuffixMode::Tag => {
+                name.strip_suffix(&format!(".{}", Self::sanitize(&suffix.tag).ok()?))
+            }
+            SuffixMode::Random | SuffixMode::Hash => {
+                let (stem, last) = name.rsplit_once('.')?;
+                let valid = |c: char| match suffix.mode {
+                    SuffixMode::Hash => matches!(c, '0'..='9' | 'a'..='f'),
+                    _ => c.is_ascii_alphanumeric(),
+                };
+                (last.len() == SUFFIX_LENGTH && last.chars().all(valid)).then_some(stem)
+            }
+        }?;
+        let codecs = Regex::new(r"\.(?:[hx]26[45]|VP9|AV1)(?:-[^-]+)?$").ok()?;
+        codecs.is_match(stem).then_some(stem)
+    }
+
+    /// Check if a file name was generated from a title, even when the title was truncated
+    ///
+    /// The title is followed by the episode of the shows, or by a technical tag of the films, so
+    /// a title starting with another one is not taken for it.
+    pub fn generated_from(config: &Config, title: &str, name: &str, show: bool) -> Result<bool> {
+        let title = Self::sanitize(title)?;
+        let reserved = Self::suffix_length(&config.suffix)?;
+        let episode = Regex::new(r"^(?:[Ss][0-9]{1,3}[Ee][0-9]{1,3}|[Ee][0-9]{1,3})$")?;
+        let stem =
+            Self::strip_suffix(&config.suffix, name).unwrap_or(name.trim_end_matches(".mkv"));
+        let name = format!("{stem}.mkv");
+
+        for (i, _) in name.match_indices('.') {
+            let (head, rest) = (&name[..i], &name[i + 1..]);
+            if !title.starts_with(head) {
+                break;
+            }
+            let token = rest.split('.').next().unwrap_or_default();
+            let tagged = match show {
+                true => episode.is_match(token),
+                false => token != "mkv" && Self::is_technical(&config.aliases, token)?,
+            };
+            // The name is the title, or the title truncated to the maximum length
+            if tagged && Self::fit(&title, rest, config.max_name_length, reserved)? == name {
+                return Ok(true);
+            }
+        }
+        Ok(false)
     }
 
     /// Parse the episode from the file name
@@ -89,25 +282,385 @@ impl Title {
             .to_string())
     }
 
+    /// Release group, parsed from the trailing '-GROUP' of the file name when none is given
+    pub fn get_group(aliases: &Aliases, group: Option<&str>, file_name: &str) -> Result<String> {
+        if let Some(group) = group {
+            return Self::sanitize(group);
+        }
+        let name = file_name.trim_end_matches(".mkv");
+        Ok(match name.rsplit_once('-') {
+            // Sources such as WEB-DL are not groups
+            Some((rest, group))
+                if !group.is_empty()
+                    && group.chars().all(|c| c.is_ascii_alphanumeric())
+                    && Self::is_technical(aliases, rest)?
+                    && Self::get_source(
+                        aliases,
+                        &format!(
+                            "{}-{group}",
+                            rest.rsplit(['.', ' ']).next().unwrap_or_default()
+                        ),
+                    )
+                    .is_err() =>
+            {
+                group.to_string()
+            }
+            _ => String::new(),
+        })
+    }
+
+    /// Check if a part of a file name ends with a technical tag, which a release group follows
+    /// unlike the hyphens of the titles such as 'Spider-Man'
+    fn is_technical(aliases: &Aliases, part: &str) -> Result<bool> {
+        let codecs = Regex::new(
+            r"(?i)(?:^|[. _\[])(?:[0-9]{3,4}[pi]|[xh]\.?26[45]|hevc|avc|av1|vp9|xvid|divx|10bit|e?ac3|aac|dts|ddp?|truehd|atmos|flac|opus|mp3)(?:[0-9]\.[0-9])?$",
+        )?;
+        let token = part.rsplit(['.', ' ', '_']).next().unwrap_or_default();
+        Ok(codecs.is_match(part)
+            || (!token.is_empty()
+                && (Self::get_source(aliases, token).is_ok()
+                    || Self::get_langs(aliases, token).is_ok()
+                    || Self::get_services(aliases, token, "").is_ok()
+                    || Self::get_editions(aliases, token, "").is_ok())))
+    }
+
     /// Langs
-    pub fn get_langs(langs: &str) -> Result<String> {
-        Ok(langs
-            .trim()
-            .split(' ')
-            .map(MatroskaLang::from)
-            .map(|l| l.to_string())
-            .join("."))
+    pub fn get_langs(aliases: &Aliases, langs: &str) -> Result<String> {
+        let langs = Self::resolve(&aliases.languages, langs, ("language", "languages"), |l| {
+            MatroskaLang::from(l).to_string()
+        })?;
+        Ok(langs.join("."))
     }
 
     /// Rip
-    pub fn get_source(source: &str) -> String {
-        let mut source: Vec<String> = source
-            .trim()
-            .split(' ')
-            .map(MatroskaSource::from)
-            .map(|r| r.to_string())
-            .collect();
+    pub fn get_source(aliases: &Aliases, source: &str) -> Result<String> {
+        let mut source = Self::resolve(&aliases.sources, source, ("source", "sources"), |s| {
+            MatroskaSource::from(s).to_string()
+        })?;
         source.sort();
-        source.iter().map(|m| m.to_string()).join(".").to_string()
+        Ok(source.join("."))
+    }
+
+    /// Streaming services, detected from the file name when none is given
+    pub fn get_services(aliases: &Aliases, services: &str, file_name: &str) -> Result<String> {
+        let services = match services.trim().is_empty() {
+            true => Self::detect(&aliases.services, file_name, |s| {
+                MatroskaService::from(s).to_string()
+            })?,
+            false => Self::resolve(&aliases.services, services, ("service", "services"), |s| {
+                MatroskaService::from(s).to_string()
+            })?,
+        };
+        Ok(services.join("."))
+    }
+
+    /// Editions, detected from the file name when none is given
+    pub fn get_editions(aliases: &Aliases, editions: &str, file_name: &str) -> Result<String> {
+        let editions = match editions.trim().is_empty() {
+            true => Self::detect(&aliases.editions, file_name, |e| {
+                MatroskaEdition::from(e).to_string()
+            })?,
+            false => Self::resolve(&aliases.editions, editions, ("edition", "editions"), |e| {
+                MatroskaEdition::from(e).to_string()
+            })?,
+        };
+        Ok(editions.join("."))
+    }
+
+    /// Display strings of the tokens, the aliases of the config come before the built-in ones
+    fn resolve(
+        aliases: &BTreeMap<String, Vec<String>>,
+        tokens: &str,
+        (kind, section): (&str, &str),
+        builtin: impl Fn(&str) -> String,
+    ) -> Result<Vec<String>> {
+        tokens
+            .split_whitespace()
+            .map(|token| {
+                Self::alias(aliases, token)
+                    .or_else(|| Some(builtin(token)).filter(|t| !t.is_empty()))
+                    .ok_or_else(|| {
+                        eyre!("Unknown {kind} '{token}' (add it to [aliases.{section}] in the config)")
+                    })
+            })
+            .collect()
+    }
+
+    /// Tags written as their display string in a file name
+    ///
+    /// Only the tokens following the year, the episode or the resolution are read, so that
+    /// the words of the title such as 'DC' or 'IMAX' are not taken as tags.
+    fn detect(
+        aliases: &BTreeMap<String, Vec<String>>,
+        file_name: &str,
+        builtin: impl Fn(&str) -> String,
+    ) -> Result<Vec<String>> {
+        let marker = Regex::new(
+            r"^(?:(?:19|20)[0-9]{2}|[Ss][0-9]{1,3}[Ee][0-9]{1,3}|[Ee][0-9]{1,3}|[0-9]{3,4}[pi])$",
+        )?;
+        let tokens: Vec<&str> = file_name
+            .trim_end_matches(".mkv")
+            .split(['.', ' ', '_', '-', '[', ']'])
+            .collect();
+        let start = tokens
+            .iter()
+            .position(|token| marker.is_match(token))
+            .map_or(tokens.len(), |i| i + 1);
+
+        Ok(tokens[start..]
+            .iter()
+            .filter(|token| aliases.contains_key(**token) || builtin(token) == **token)
+            .filter(|token| !token.is_empty())
+            .unique()
+            .map(|token| token.to_string())
+            .collect())
+    }
+
+    /// Display string of a token from the aliases of the config
+    fn alias(aliases: &BTreeMap<String, Vec<String>>, token: &str) -> Option<String> {
+        aliases
+            .iter()
+            .find(|(name, tokens)| {
+                name.eq_ignore_ascii_case(token)
+                    || tokens.iter().any(|t| t.eq_ignore_ascii_case(token))
+            })
+            .map(|(name, _)| name.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn services(file_name: &str) -> String {
+        Title::get_services(&Aliases::default(), "", file_name).unwrap()
+    }
+
+    fn editions(file_name: &str) -> String {
+        Title::get_editions(&Aliases::default(), "", file_name).unwrap()
+    }
+
+    #[test]
+    fn sanitize_titles() {
+        assert_eq!(
+            Title::sanitize("Some Film (2023)").unwrap(),
+            "Some.Film.(2023)"
+        );
+        assert_eq!(
+            Title::sanitize("Amélie: Le Film").unwrap(),
+            "Amelie.Le.Film"
+        );
+        assert_eq!(Title::sanitize("Show - Part 1").unwrap(), "Show-Part.1");
+        assert_eq!(Title::sanitize(" _Film__Name_. ").unwrap(), "Film.Name");
+        assert_eq!(Title::sanitize("?*/").unwrap(), "");
+    }
+
+    #[test]
+    fn sanitize_filters_the_transliterations() {
+        let title = Title::sanitize("Episode ½").unwrap();
+        assert!(!title.contains('/'), "{title}");
+        assert_eq!(title, "Episode.12");
+        assert_eq!(Title::sanitize("Part ⑴").unwrap(), "Part.(1)");
+        assert_eq!(Title::sanitize("Ærø Straße").unwrap(), "AEro.Strasse");
+    }
+
+    #[test]
+    fn fit_truncates_the_title() {
+        assert_eq!(
+            Title::fit("Film", "2023.mkv", 0, 0).unwrap(),
+            "Film.2023.mkv"
+        );
+        assert_eq!(
+            Title::fit("Film", "2023.mkv", 13, 0).unwrap(),
+            "Film.2023.mkv"
+        );
+        assert_eq!(
+            Title::fit("Long.Film", "2023.mkv", 15, 0).unwrap(),
+            "Long.F.2023.mkv"
+        );
+        assert_eq!(
+            Title::fit("Long.Film", "2023.mkv", 14, 0).unwrap(),
+            "Long.2023.mkv"
+        );
+        assert!(Title::fit("", "2023.mkv", 0, 0).is_err());
+        assert!(Title::fit("Film", "2023.mkv", 9, 0).is_err());
+    }
+
+    #[test]
+    fn fit_reserves_room_for_the_suffix() {
+        let name = Title::fit("Long.Film", "2023.mkv", 23, 8).unwrap();
+        assert_eq!(name, "Long.F.2023.mkv");
+        assert_eq!(Title::suffixed_name(&name, "aB3dE5f").len(), 23);
+        assert!(Title::fit("Film", "2023.mkv", 17, 8).is_err());
+    }
+
+    fn suffix(mode: SuffixMode, tag: &str) -> Suffix {
+        Suffix {
+            mode,
+            tag: tag.to_string(),
+            file_name: true,
+        }
+    }
+
+    #[test]
+    fn strip_the_file_name_suffix() {
+        let random = suffix(SuffixMode::Random, "");
+        assert_eq!(
+            Title::strip_suffix(&random, "Film.2023.h264.aB3dE5f.mkv"),
+            Some("Film.2023.h264")
+        );
+        assert_eq!(Title::strip_suffix(&random, "Film.2023.h264.mkv"), None);
+        let hash = suffix(SuffixMode::Hash, "");
+        assert_eq!(
+            Title::strip_suffix(&hash, "Film.2023.h264.0a1b2c3.mkv"),
+            Some("Film.2023.h264")
+        );
+        assert_eq!(Title::strip_suffix(&hash, "Film.2023.UNRATED.mkv"), None);
+        assert_eq!(
+            Title::strip_suffix(&random, "Film.2023.h264-GRP.aB3dE5f.mkv"),
+            Some("Film.2023.h264-GRP")
+        );
+
+        // Only after the video codecs, where the uploads write it
+        assert_eq!(
+            Title::strip_suffix(&random, "Film.2023.1080p.UNRATED.mkv"),
+            None
+        );
+        assert_eq!(
+            Title::strip_suffix(&random, "Film.2023.1080p.HDLight.mkv"),
+            None
+        );
+        let tag = suffix(SuffixMode::Tag, "my tag");
+        assert_eq!(
+            Title::strip_suffix(&tag, "Film.2023.h264.my.tag.mkv"),
+            Some("Film.2023.h264")
+        );
+        assert_eq!(Title::strip_suffix(&tag, "Film.2023.h264.mkv"), None);
+        let none = suffix(SuffixMode::None, "");
+        assert_eq!(Title::strip_suffix(&none, "Film.2023.aB3dE5f.mkv"), None);
+    }
+
+    #[test]
+    fn same_name_with_a_suffix() {
+        let random = suffix(SuffixMode::Random, "");
+        assert!(Title::same_name(&random, "Film.h264.mkv", "Film.h264.mkv"));
+        assert!(Title::same_name(
+            &random,
+            "Film.h264.aB3dE5f.mkv",
+            "Film.h264.mkv"
+        ));
+        assert!(!Title::same_name(
+            &random,
+            "Film.h264.VFF.mkv",
+            "Film.h264.mkv"
+        ));
+        let plain = Suffix {
+            file_name: false,
+            ..random
+        };
+        assert!(!Title::same_name(
+            &plain,
+            "Film.h264.aB3dE5f.mkv",
+            "Film.h264.mkv"
+        ));
+    }
+
+    #[test]
+    fn generated_from_the_title() {
+        let config = Config::default();
+        let show = |title, name| Title::generated_from(&config, title, name, true).unwrap();
+        assert!(show("My Show", "My.Show.s01e01.1080p.EAC3.h264.mkv"));
+        assert!(!show("My Show", "My.Show.US.s01e01.1080p.EAC3.h264.mkv"));
+        assert!(!show("My Show US", "My.Show.s01e01.1080p.EAC3.h264.mkv"));
+        assert!(!show("My Show", "Other.Show.s01e01.1080p.EAC3.h264.mkv"));
+
+        let film = |title, name| Title::generated_from(&config, title, name, false).unwrap();
+        assert!(film("My Film", "My.Film.1080p.EAC3.h264.mkv"));
+        assert!(film("My Film", "My.Film.MULTi.1080p.EAC3.h264.mkv"));
+        assert!(!film("My Film", "My.Film.2.1080p.EAC3.h264.mkv"));
+        assert!(!film("My Film", "My.Film.mkv"));
+    }
+
+    #[test]
+    fn generated_from_a_truncated_title() {
+        let config = Config {
+            max_name_length: 30,
+            ..Config::default()
+        };
+        let show = |title, name| Title::generated_from(&config, title, name, true).unwrap();
+        let name = Title::fit("A.Very.Long.Show", "s01e01.h264.mkv", 30, 0).unwrap();
+        assert_eq!(name, "A.Very.Long.Sh.s01e01.h264.mkv");
+        assert!(show("A Very Long Show", &name));
+        assert!(!show("A Very Long", &name));
+        assert!(!show("A Very Long Show", "A.Very.s01e01.h264.mkv"));
+    }
+
+    #[test]
+    fn generated_from_with_a_file_name_suffix() {
+        let config = Config {
+            suffix: suffix(SuffixMode::Random, ""),
+            ..Config::default()
+        };
+        assert!(
+            Title::generated_from(&config, "My Show", "My.Show.s01e01.h264.aB3dE5f.mkv", true)
+                .unwrap()
+        );
+    }
+
+    fn group(file_name: &str) -> String {
+        Title::get_group(&Aliases::default(), None, file_name).unwrap()
+    }
+
+    #[test]
+    fn group_follows_a_technical_tag() {
+        assert_eq!(group("Some.Show.S01E02.1080p.AMZN.WEB-DL-GRP.mkv"), "GRP");
+        assert_eq!(group("Film.2023.1080p.x264-GRP.mkv"), "GRP");
+        assert_eq!(group("Film.2023.1080p-GRP.mkv"), "GRP");
+        assert_eq!(group("Film.2023.BluRay.DDP5.1-GRP.mkv"), "GRP");
+        assert_eq!(group("Film.2023.MULTi-GRP.mkv"), "GRP");
+    }
+
+    #[test]
+    fn group_ignores_the_hyphens_of_the_title() {
+        assert_eq!(group("X-Men.mkv"), "");
+        assert_eq!(group("Spider-Man.2002.mkv"), "");
+        assert_eq!(group("Spider-Man.mkv"), "");
+        assert_eq!(group("Film.2023.WEB-DL.mkv"), "");
+        assert_eq!(group("Film.2023-GRP.mkv"), "");
+    }
+
+    #[test]
+    fn group_override() {
+        let aliases = Aliases::default();
+        assert_eq!(
+            Title::get_group(&aliases, Some("MINE"), "Film-GRP.mkv").unwrap(),
+            "MINE"
+        );
+        assert_eq!(
+            Title::get_group(&aliases, Some(""), "Film.x264-GRP.mkv").unwrap(),
+            ""
+        );
+        assert_eq!(
+            Title::get_group(&aliases, Some(" My/Group "), "Film.mkv").unwrap(),
+            "MyGroup"
+        );
+    }
+
+    #[test]
+    fn detect_tags_after_the_episode() {
+        let name = "Some.Show.S01E02.REPACK.1080p.AMZN.WEB-DL-GRP.mkv";
+        assert_eq!(services(name), "AMZN");
+        assert_eq!(editions(name), "REPACK");
+    }
+
+    #[test]
+    fn detect_ignores_the_title() {
+        assert_eq!(
+            editions("DC.League.Of.Super.Pets.2022.1080p.WEB-DL.mkv"),
+            ""
+        );
+        assert_eq!(editions("IMAX.Story.2019.IMAX.1080p.mkv"), "IMAX");
+        assert_eq!(services("NF.Story.1080p.NF.WEB-DL.mkv"), "NF");
+        assert_eq!(editions("DC.REPACK.mkv"), "");
     }
 }
@@ -1,51 +1,59 @@
-use reqwest::{
-    multipart::{Form, Part},
-    Body, Client, Response,
-};
+use futures_util::StreamExt;
+use reqwest::Body;
+use sha2::{Digest, Sha256};
+use std::sync::{Arc, Mutex};
 use tokio::fs::File;
 use tokio_util::codec::{BytesCodec, FramedRead};
 
-use rs_uptobox::Uptobox;
-
-use crate::{eyre, Result};
+use crate::{eyre, limit::Limiter, storage::RemoteStorage, Result};
 
 pub struct Upload {}
 
-/// Upload the file to uptobox
+/// Upload the file to the remote storage
 impl Upload {
-    pub async fn start<'a>(
-        client: &Client,
-        uptobox: &Uptobox,
+    pub async fn start(
+        storage: &dyn RemoteStorage,
+        limiter: &Arc<Limiter>,
         path: &str,
         title: &str,
-    ) -> Result<Response> {
-        let url = uptobox.get_upload_url().await?.upload_link;
-        let url = format!("https://{}", &url[2..]);
-
+    ) -> Result<String> {
         let file: File = File::open(path)
             .await
             .map_err(|e| eyre!("Unable to open the file '{path}' ({e})"))?;
 
-        let stream = FramedRead::new(file, BytesCodec::new());
+        // Hash and throttle the file while it is read
+        let hasher = Arc::new(Mutex::new(Sha256::new()));
+        let stream = FramedRead::new(file, BytesCodec::new())
+            .inspect({
+                let hasher = hasher.clone();
+                move |chunk| {
+                    if let (Ok(chunk), Ok(mut hasher)) = (chunk, hasher.lock()) {
+                        hasher.update(chunk);
+                    }
+                }
+            })
+            .then({
+                let limiter = limiter.clone();
+                move |chunk| {
+                    let limiter = limiter.clone();
+                    async move {
+                        if let Ok(chunk) = &chunk {
+                            limiter.acquire(chunk.len()).await;
+                        }
+                        chunk
+                    }
+                }
+            });
         let body = Body::wrap_stream(stream);
 
-        let file = Part::stream(body)
-            .file_name(title.to_owned())
-            .mime_str("video/x-matroska")?;
-
-        let form = Form::new().part("file", file);
-
-        let res = client
-            .post(url)
-            .multipart(form)
-            .send()
+        storage
+            .upload(body, title)
             .await
             .map_err(|e| eyre!("Unable to upload the file '{path}' ({e})"))?;
 
-        if res.status().is_success() {
-            Ok(res)
-        } else {
-            Err(eyre!("Unable to upload the file '{path}' (Unknown error)"))
-        }
+        let hasher = hasher
+            .lock()
+            .map_err(|_| eyre!("Unable to hash the file '{path}'"))?;
+        Ok(format!("{:x}", hasher.clone().finalize()))
     }
 }
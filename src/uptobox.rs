@@ -1,25 +1,25 @@
 use chrono::DateTime;
-use rs_uptobox::{GetFiles, GetFilesFiles, GetFilesResponse, Uptobox as UptoboxApi};
 use std::cmp::Ordering;
 
-use crate::{eyre, Result};
+use crate::{
+    eyre,
+    storage::{RemoteFile, RemoteFolder, RemoteListing, RemoteStorage},
+    Result,
+};
 
 pub struct Uptobox {}
 
 impl Uptobox {
     /// Get files
-    pub async fn get_files(uptobox: &UptoboxApi) -> Result<GetFilesResponse> {
-        uptobox
-            .get_files(&GetFiles::new("//"))
+    pub async fn get_files(storage: &dyn RemoteStorage) -> Result<RemoteListing> {
+        storage
+            .list("//")
             .await
             .map_err(|e| eyre!("Unable to fetch files from Uptobox ({e})"))
     }
 
     /// Get the uploaded file
-    pub fn get_uploaded_file<'a>(
-        files: &'a GetFilesResponse,
-        name: &str,
-    ) -> Result<&'a GetFilesFiles> {
+    pub fn get_uploaded_file<'a>(files: &'a RemoteListing, name: &str) -> Result<&'a RemoteFile> {
         files
             .files
             .iter()
@@ -38,23 +38,23 @@ impl Uptobox {
 
     /// Get destination directory
     pub async fn get_destination_directory(
-        uptobox: &UptoboxApi,
+        storage: &dyn RemoteStorage,
         path: &str,
-    ) -> Result<GetFilesResponse> {
-        uptobox
-            .get_files(&GetFiles::new(path))
+    ) -> Result<RemoteListing> {
+        storage
+            .list(path)
             .await
             .map_err(|e| eyre!("Unable to fetch destination folder on uptobox ({e})"))
     }
 
     /// Move files to destination
     pub async fn move_files_to_destination(
-        uptobox: &UptoboxApi,
+        storage: &dyn RemoteStorage,
         file_code: &str,
-        destination: usize,
-    ) -> Result<usize> {
-        uptobox
-            .move_files(vec![file_code], destination)
+        destination: &RemoteFolder,
+    ) -> Result<()> {
+        storage
+            .move_files(&[file_code], destination)
             .await
             .map_err(|e| {
                 eyre!("Unable to move uploaded file to destination folder on uptobox ({e})")
@@ -0,0 +1,100 @@
+use reqwest::{header::RANGE, Client, StatusCode};
+use std::{
+    cmp::min,
+    fs::{self, File},
+    io::{Read, Seek, SeekFrom},
+};
+
+use crate::{eyre, storage::RemoteStorage, Result};
+
+pub struct Verify {}
+
+/// Verify the uploaded file against the local file
+impl Verify {
+    pub async fn start(
+        client: &Client,
+        storage: &dyn RemoteStorage,
+        file_code: &str,
+        path: &str,
+        sample: u64,
+    ) -> Result<()> {
+        let local_size = fs::metadata(path)
+            .map_err(|e| eyre!("Unable to read the size of '{path}' ({e})"))?
+            .len();
+
+        // Compare the size of the uploaded file
+        let remote_size = storage
+            .get_file(file_code)
+            .await
+            .map_err(|e| eyre!("Unable to retreive informations for '{file_code}' ({e})"))?
+            .file_size;
+        if local_size != remote_size {
+            return Err(eyre!(
+                "The uploaded file does not match '{path}' (local: {local_size} bytes, remote: {remote_size} bytes)"
+            ));
+        }
+
+        if sample == 0 {
+            return Ok(());
+        }
+
+        // Get the file url
+        let url = storage
+            .get_download_url(file_code)
+            .await
+            .map_err(|e| eyre!("Unable to fetch the download link for '{file_code}' ({e})"))?;
+
+        // Compare the first and the last bytes of the file
+        let sample = min(sample * 1024 * 1024, local_size);
+        Self::compare_range((client, storage), &url, path, 0, sample).await?;
+        Self::compare_range((client, storage), &url, path, local_size - sample, sample).await?;
+
+        Ok(())
+    }
+
+    /// Compare a range of the remote file with the local file
+    async fn compare_range(
+        (client, storage): (&Client, &dyn RemoteStorage),
+        url: &str,
+        path: &str,
+        start: u64,
+        length: u64,
+    ) -> Result<()> {
+        if length == 0 {
+            return Ok(());
+        }
+
+        let res = storage
+            .download(client, url)
+            .header(RANGE, format!("bytes={}-{}", start, start + length - 1))
+            .send()
+            .await
+            .map_err(|e| eyre!("Unable to download '{url}' ({e})"))?;
+        if res.status() != StatusCode::PARTIAL_CONTENT {
+            return Err(eyre!(
+                "Unable to download a range of '{url}' (status: {})",
+                res.status()
+            ));
+        }
+        let remote = res
+            .bytes()
+            .await
+            .map_err(|e| eyre!("Error while downloading '{url}' ({e})"))?;
+
+        let mut local = vec![0; length as usize];
+        let mut file =
+            File::open(path).map_err(|e| eyre!("Unable to open the file '{path}' ({e})"))?;
+        file.seek(SeekFrom::Start(start))
+            .and_then(|_| file.read_exact(&mut local))
+            .map_err(|e| eyre!("Unable to read the file '{path}' ({e})"))?;
+
+        if remote.as_ref() != local.as_slice() {
+            return Err(eyre!(
+                "The uploaded file does not match '{path}' (bytes {start} to {})",
+                start + length - 1
+            ));
+        }
+
+        Ok(())
+    }
+}
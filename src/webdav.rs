@@ -0,0 +1,321 @@
+use async_trait::async_trait;
+use chrono::DateTime;
+use percent_encoding::percent_decode_str;
+use reqwest::{header::HeaderValue, Body, Client, Method, RequestBuilder, Response, Url};
+use roxmltree::{Document, Node};
+
+use crate::{
+    eyre,
+    remote::Remote,
+    storage::{RemoteAccount, RemoteFile, RemoteFolder, RemoteListing, RemoteStorage},
+    Result,
+};
+
+/// Properties requested when listing a collection
+const PROPFIND: &str = r#"<?xml version="1.0" encoding="utf-8"?><d:propfind xmlns:d="DAV:"><d:prop><d:resourcetype/><d:getcontentlength/><d:getlastmodified/></d:prop></d:propfind>"#;
+
+/// Storage on a WebDAV share, the file codes are the remote paths of the files
+pub struct WebdavStorage {
+    client: Client,
+    url: String,
+    username: String,
+    password: String,
+}
+
+/// Resource of a PROPFIND response
+struct WebdavEntry {
+    path: String,
+    is_dir: bool,
+    size: u64,
+    modified: String,
+}
+
+impl WebdavStorage {
+    pub fn new(client: Client, url: &str, username: &str, password: &str) -> Self {
+        Self {
+            client,
+            url: url.trim_end_matches('/').to_string(),
+            username: username.to_string(),
+            password: password.to_string(),
+        }
+    }
+
+    /// Url of a remote path
+    fn url(&self, path: &str) -> Result<Url> {
+        let mut url =
+            Url::parse(&self.url).map_err(|e| eyre!("Invalid WebDAV url '{}' ({e})", self.url))?;
+        url.path_segments_mut()
+            .map_err(|_| eyre!("Invalid WebDAV url '{}'", self.url))?
+            .pop_if_empty()
+            .extend(path.split('/').filter(|s| !s.is_empty()));
+        Ok(url)
+    }
+
+    /// Remote path of an url or of the path of an url
+    fn path(&self, href: &str) -> Result<String> {
+        let href = Url::parse(href)
+            .map(|url| url.path().to_string())
+            .unwrap_or_else(|_| href.to_string());
+        let base = self.url("//")?;
+        let href = percent_decode_str(&href).decode_utf8_lossy();
+        let base = percent_decode_str(base.path()).decode_utf8_lossy();
+        let path = href
+            .strip_prefix(base.trim_end_matches('/'))
+            .ok_or_else(|| eyre!("'{href}' is not on the WebDAV share"))?;
+        Ok(Remote::normalize(path))
+    }
+
+    /// Folder at a remote path
+    fn folder(path: &str) -> RemoteFolder {
+        RemoteFolder {
+            fld_id: 0,
+            fld_name: path.to_string(),
+            name: path.rsplit('/').next().unwrap_or_default().to_string(),
+        }
+    }
+
+    /// Request authenticated with the credentials of the share
+    fn request(&self, method: Method, url: Url) -> RequestBuilder {
+        self.authorize(self.client.request(method, url))
+    }
+
+    /// Add the credentials of the share to a request
+    fn authorize(&self, request: RequestBuilder) -> RequestBuilder {
+        match self.username.is_empty() {
+            true => request,
+            false => request.basic_auth(&self.username, Some(&self.password)),
+        }
+    }
+
+    /// Send a request, failing on an error status
+    async fn send(&self, request: RequestBuilder) -> Result<Response> {
+        let res = request.send().await?;
+        match res.status().is_success() {
+            true => Ok(res),
+            false => Err(eyre!("{}", res.status())),
+        }
+    }
+
+    /// Move a resource to another remote path
+    async fn relocate(&self, from: &str, to: &str) -> Result<()> {
+        let destination = HeaderValue::from_str(self.url(to)?.as_str())?;
+        self.send(
+            self.request(Method::from_bytes(b"MOVE")?, self.url(from)?)
+                .header("Destination", destination)
+                .header("Overwrite", "F"),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Properties of a resource, and of its children with a depth of 1
+    async fn propfind(&self, path: &str, depth: &str) -> Result<Vec<WebdavEntry>> {
+        let body = self
+            .send(
+                self.request(Method::from_bytes(b"PROPFIND")?, self.url(path)?)
+                    .header("Depth", depth)
+                    .header("Content-Type", "application/xml")
+                    .body(PROPFIND),
+            )
+            .await?
+            .text()
+            .await?;
+        let document =
+            Document::parse(&body).map_err(|e| eyre!("Invalid WebDAV response ({e})"))?;
+
+        let mut entries = vec![];
+        for response in document
+            .descendants()
+            .filter(|n| Self::is_dav(n, "response"))
+        {
+            let href = Self::property(response, "href")
+                .and_then(|n| n.text())
+                .ok_or_else(|| eyre!("Invalid WebDAV response (missing href)"))?;
+            let modified = Self::property(response, "getlastmodified")
+                .and_then(|n| n.text())
+                .and_then(|date| DateTime::parse_from_rfc2822(date).ok())
+                .map(|date| date.format("%Y-%m-%d %H:%M:%S").to_string())
+                .unwrap_or_default();
+            entries.push(WebdavEntry {
+                path: self.path(href)?,
+                is_dir: Self::property(response, "collection").is_some(),
+                size: Self::property(response, "getcontentlength")
+                    .and_then(|n| n.text())
+                    .and_then(|size| size.trim().parse().ok())
+                    .unwrap_or(0),
+                modified,
+            });
+        }
+        Ok(entries)
+    }
+
+    /// First element of the DAV namespace with a name
+    fn property<'a, 'i>(node: Node<'a, 'i>, name: &str) -> Option<Node<'a, 'i>> {
+        node.descendants().find(|n| Self::is_dav(n, name))
+    }
+
+    /// Check if a node is an element of the DAV namespace
+    fn is_dav(node: &Node, name: &str) -> bool {
+        node.tag_name().namespace() == Some("DAV:") && node.tag_name().name() == name
+    }
+}
+
+#[async_trait]
+impl RemoteStorage for WebdavStorage {
+    /// Links are remote paths or urls on the share
+    fn file_code(&self, link: &str) -> Result<String> {
+        match link.starts_with("//") {
+            true => Ok(Remote::normalize(link)),
+            false => self.path(link),
+        }
+    }
+
+    async fn get_file(&self, file_code: &str) -> Result<RemoteFile> {
+        let entry = self
+            .propfind(file_code, "0")
+            .await
+            .map_err(|e| eyre!("Unable to find '{file_code}' on the WebDAV share ({e})"))?
+            .into_iter()
+            .find(|e| !e.is_dir)
+            .ok_or_else(|| eyre!("'{file_code}' is not a file"))?;
+        Ok(RemoteFile {
+            file_name: entry
+                .path
+                .rsplit('/')
+                .next()
+                .unwrap_or_default()
+                .to_string(),
+            file_code: entry.path,
+            file_size: entry.size,
+            file_created: entry.modified,
+        })
+    }
+
+    async fn get_download_url(&self, file_code: &str) -> Result<String> {
+        Ok(self.url(file_code)?.to_string())
+    }
+
+    /// The credentials are sent with the download requests
+    fn download(&self, client: &Client, url: &str) -> RequestBuilder {
+        self.authorize(client.get(url))
+    }
+
+    fn private_downloads(&self) -> bool {
+        !self.username.is_empty()
+    }
+
+    async fn upload(&self, body: Body, name: &str) -> Result<()> {
+        self.send(
+            self.request(Method::PUT, self.url(&Remote::join("//", name))?)
+                .body(body),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn list(&self, path: &str) -> Result<RemoteListing> {
+        let path = Remote::normalize(path);
+        let entries = self
+            .propfind(&path, "1")
+            .await
+            .map_err(|e| eyre!("Unable to list '{path}' on the WebDAV share ({e})"))?;
+
+        let mut listing = RemoteListing {
+            current_folder: Self::folder(&path),
+            folders: vec![],
+            files: vec![],
+        };
+        for entry in entries.into_iter().filter(|e| e.path != path) {
+            match entry.is_dir {
+                true => listing.folders.push(Self::folder(&entry.path)),
+                false => listing.files.push(RemoteFile {
+                    file_name: entry
+                        .path
+                        .rsplit('/')
+                        .next()
+                        .unwrap_or_default()
+                        .to_string(),
+                    file_code: entry.path,
+                    file_size: entry.size,
+                    file_created: entry.modified,
+                }),
+            }
+        }
+        Ok(listing)
+    }
+
+    async fn create_folder(&self, path: &str, name: &str) -> Result<()> {
+        let url = self.url(&Remote::join(path, name))?;
+        self.send(self.request(Method::from_bytes(b"MKCOL")?, url))
+            .await
+            .map_err(|e| eyre!("Unable to create the folder '{name}' in '{path}' ({e})"))?;
+        Ok(())
+    }
+
+    async fn move_files(&self, file_codes: &[&str], destination: &RemoteFolder) -> Result<()> {
+        for file_code in file_codes {
+            let name = file_code.rsplit('/').next().unwrap_or_default();
+            self.relocate(file_code, &Remote::join(&destination.fld_name, name))
+                .await
+                .map_err(|e| eyre!("Unable to move '{file_code}' on the WebDAV share ({e})"))?;
+        }
+        Ok(())
+    }
+
+    async fn move_folder(&self, folder: &RemoteFolder, destination: &RemoteFolder) -> Result<()> {
+        let path = Remote::join(&destination.fld_name, &Remote::folder_name(folder));
+        self.relocate(&folder.fld_name, &path).await.map_err(|e| {
+            eyre!(
+                "Unable to move '{}' on the WebDAV share ({e})",
+                folder.fld_name
+            )
+        })
+    }
+
+    async fn rename_file(&self, file_code: &str, name: &str) -> Result<()> {
+        let parent = file_code.rsplit_once('/').unwrap_or_default().0;
+        self.relocate(file_code, &Remote::join(parent, name))
+            .await
+            .map_err(|e| eyre!("Unable to rename '{file_code}' to '{name}' ({e})"))
+    }
+
+    async fn rename_folder(&self, folder: &RemoteFolder, name: &str) -> Result<()> {
+        let parent = folder.fld_name.rsplit_once('/').unwrap_or_default().0;
+        self.relocate(&folder.fld_name, &Remote::join(parent, name))
+            .await
+            .map_err(|e| eyre!("Unable to rename the folder to '{name}' ({e})"))
+    }
+
+    async fn delete_files(&self, file_codes: &[&str]) -> Result<()> {
+        for file_code in file_codes {
+            self.send(self.request(Method::DELETE, self.url(file_code)?))
+                .await
+                .map_err(|e| eyre!("Unable to delete '{file_code}' on the WebDAV share ({e})"))?;
+        }
+        Ok(())
+    }
+
+    async fn delete_folder(&self, folder: &RemoteFolder) -> Result<()> {
+        self.send(self.request(Method::DELETE, self.url(&folder.fld_name)?))
+            .await
+            .map_err(|e| {
+                eyre!(
+                    "Unable to delete '{}' on the WebDAV share ({e})",
+                    folder.fld_name
+                )
+            })?;
+        Ok(())
+    }
+
+    /// Shares have no subscription, the account is the user of the share
+    async fn get_account(&self) -> Result<RemoteAccount> {
+        self.propfind("//", "0").await?;
+        Ok(RemoteAccount {
+            login: self.username.clone(),
+            email: String::new(),
+            point: String::new(),
+            premium_expire: String::new(),
+            premium: true,
+        })
+    }
+}
@@ -0,0 +1,649 @@
+//! Mock Uptobox server and environment used by the end-to-end tests
+
+#![allow(dead_code)]
+
+use axum::{
+    body::Bytes,
+    extract::{DefaultBodyLimit, Multipart, Path, Query, Request, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use dav_server::{fakels::FakeLs, localfs::LocalFs, DavHandler};
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    fs,
+    net::SocketAddr,
+    os::unix::fs::PermissionsExt,
+    path::PathBuf,
+    process::{Output, Stdio},
+    sync::{Arc, Mutex, MutexGuard},
+};
+use tempfile::TempDir;
+use tokio::{
+    io::AsyncWriteExt,
+    net::TcpListener,
+    process::{Child, Command},
+};
+
+pub const TOKEN: &str = "mock-token";
+
+/// Tracks printed by the fake mkvmerge
+const MKVMERGE_TRACKS: &str = r#"{"tracks":[{"codec":"AVC/H.264/MPEG-4p10","type":"video","properties":{"pixel_dimensions":"1920x1080"}},{"codec":"E-AC-3","type":"audio","properties":{}}]}"#;
+
+#[derive(Clone)]
+pub struct MockFile {
+    pub code: String,
+    pub name: String,
+    pub folder: usize,
+    pub data: Vec<u8>,
+}
+
+#[derive(Clone)]
+pub struct MockFolder {
+    pub id: usize,
+    pub parent: Option<usize>,
+    pub name: String,
+}
+
+pub struct MockState {
+    pub premium: bool,
+    pub files: Vec<MockFile>,
+    pub folders: Vec<MockFolder>,
+    addr: SocketAddr,
+    next_id: usize,
+}
+
+impl MockState {
+    /// Full path of a folder
+    fn path(&self, id: usize) -> String {
+        let folder = self.folders.iter().find(|f| f.id == id);
+        match folder.and_then(|f| f.parent.map(|p| (p, &f.name))) {
+            Some((parent, name)) => match self.path(parent).as_str() {
+                "//" => format!("//{name}"),
+                path => format!("{path}/{name}"),
+            },
+            None => String::from("//"),
+        }
+    }
+
+    /// Folder at a path
+    fn folder(&self, path: &str) -> Option<usize> {
+        let path = format!("//{}", path.trim_matches('/'));
+        self.folders
+            .iter()
+            .map(|f| f.id)
+            .find(|id| self.path(*id) == path)
+    }
+
+    fn next_id(&mut self) -> usize {
+        self.next_id += 1;
+        self.next_id
+    }
+}
+
+/// Uptobox API emulated on a local port
+pub struct MockServer {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockServer {
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let state = Arc::new(Mutex::new(MockState {
+            premium: true,
+            files: vec![],
+            folders: vec![MockFolder {
+                id: 0,
+                parent: None,
+                name: String::new(),
+            }],
+            addr: listener.local_addr().unwrap(),
+            next_id: 0,
+        }));
+
+        let app = Router::new()
+            .route("/api/user/me", get(account))
+            .route("/api/link/info", get(file_infos))
+            .route("/api/link", get(download_link))
+            .route("/api/upload", get(upload_link))
+            .route(
+                "/api/user/files",
+                get(list).patch(edit).put(create_folder).delete(delete),
+            )
+            .route("/dl/:code", get(download))
+            .route("/upload", post(upload))
+            .layer(DefaultBodyLimit::disable())
+            .with_state(state.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        Self { state }
+    }
+
+    /// Base url of the API
+    pub fn url(&self) -> String {
+        format!("http://{}/api", self.state().addr)
+    }
+
+    pub fn state(&self) -> MutexGuard<'_, MockState> {
+        self.state.lock().unwrap()
+    }
+
+    /// Create a folder and its parents
+    pub fn add_folder(&self, path: &str) -> usize {
+        let mut state = self.state();
+        let mut parent = 0;
+        for name in path.split('/').filter(|n| !n.is_empty()) {
+            let existing = state
+                .folders
+                .iter()
+                .find(|f| f.parent == Some(parent) && f.name == name)
+                .map(|f| f.id);
+            parent = match existing {
+                Some(id) => id,
+                None => {
+                    let id = state.next_id();
+                    state.folders.push(MockFolder {
+                        id,
+                        parent: Some(parent),
+                        name: name.to_string(),
+                    });
+                    id
+                }
+            };
+        }
+        parent
+    }
+
+    /// Add a file to a folder
+    pub fn add_file(&self, code: &str, name: &str, folder: &str, data: Vec<u8>) {
+        let folder = self.add_folder(folder);
+        self.state().files.push(MockFile {
+            code: code.to_string(),
+            name: name.to_string(),
+            folder,
+            data,
+        });
+    }
+
+    /// Files of a folder
+    pub fn files_in(&self, path: &str) -> Vec<MockFile> {
+        let state = self.state();
+        let folder = state.folder(path);
+        state
+            .files
+            .iter()
+            .filter(|f| Some(f.folder) == folder)
+            .cloned()
+            .collect()
+    }
+}
+
+type SharedState = State<Arc<Mutex<MockState>>>;
+
+fn success(data: Value) -> Json<Value> {
+    Json(json!({ "statusCode": 0, "message": "Success", "data": data }))
+}
+
+fn failure(message: &str) -> Json<Value> {
+    Json(json!({ "statusCode": 1, "message": message }))
+}
+
+fn authorized(token: Option<&Value>) -> bool {
+    token.and_then(Value::as_str) == Some(TOKEN)
+}
+
+async fn account(
+    State(state): SharedState,
+    Query(query): Query<HashMap<String, Value>>,
+) -> Json<Value> {
+    if !authorized(query.get("token")) {
+        return failure("Invalid token");
+    }
+    let premium = state.lock().unwrap().premium;
+    success(json!({
+        "login": "mock",
+        "email": "mock@example.com",
+        "point": "12",
+        "premium_expire": "2099-01-01 00:00:00",
+        "premium": premium as u8,
+    }))
+}
+
+async fn file_infos(
+    State(state): SharedState,
+    Query(query): Query<HashMap<String, String>>,
+) -> Json<Value> {
+    let state = state.lock().unwrap();
+    let codes = query.get("fileCodes").cloned().unwrap_or_default();
+    let list: Vec<Value> = codes
+        .split(',')
+        .filter_map(|code| state.files.iter().find(|f| f.code == code))
+        .map(|f| json!({ "file_code": f.code, "file_name": f.name, "file_size": f.data.len() }))
+        .collect();
+    success(json!({ "list": list }))
+}
+
+async fn download_link(
+    State(state): SharedState,
+    Query(query): Query<HashMap<String, Value>>,
+) -> Json<Value> {
+    if !authorized(query.get("token")) {
+        return failure("Invalid token");
+    }
+    let state = state.lock().unwrap();
+    if !state.premium {
+        return Json(
+            json!({ "statusCode": 16, "message": "Waiting needed", "data": { "waiting": 30 } }),
+        );
+    }
+    match query.get("file_code").and_then(Value::as_str) {
+        Some(code) if state.files.iter().any(|f| f.code == code) => {
+            success(json!({ "dlLink": format!("http://{}/dl/{code}", state.addr) }))
+        }
+        _ => failure("File not found"),
+    }
+}
+
+async fn upload_link(
+    State(state): SharedState,
+    Query(query): Query<HashMap<String, Value>>,
+) -> Json<Value> {
+    if !authorized(query.get("token")) {
+        return failure("Invalid token");
+    }
+    let addr = state.lock().unwrap().addr;
+    success(json!({ "uploadLink": format!("//{addr}/upload") }))
+}
+
+async fn list(
+    State(state): SharedState,
+    Query(query): Query<HashMap<String, String>>,
+) -> Json<Value> {
+    if query.get("token").map(String::as_str) != Some(TOKEN) {
+        return failure("Invalid token");
+    }
+    let state = state.lock().unwrap();
+    let path = query.get("path").cloned().unwrap_or_default();
+    let Some(id) = state.folder(&path) else {
+        return failure("Folder not found");
+    };
+    let limit: usize = query
+        .get("limit")
+        .and_then(|l| l.parse().ok())
+        .unwrap_or(10);
+    let offset: usize = query
+        .get("offset")
+        .and_then(|o| o.parse().ok())
+        .unwrap_or(0);
+
+    let folders: Vec<Value> = state
+        .folders
+        .iter()
+        .filter(|f| f.parent == Some(id))
+        .map(|f| json!({ "fld_id": f.id, "fld_name": state.path(f.id), "name": f.name }))
+        .collect();
+    let files: Vec<Value> = state
+        .files
+        .iter()
+        .filter(|f| f.folder == id)
+        .skip(offset)
+        .take(limit)
+        .map(|f| {
+            json!({
+                "file_code": f.code,
+                "file_name": f.name,
+                "file_size": f.data.len(),
+                "file_created": "2024-01-01 00:00:00",
+            })
+        })
+        .collect();
+
+    success(json!({
+        "currentFolder": { "fld_id": id, "fld_name": state.path(id), "name": "" },
+        "folders": folders,
+        "files": files,
+    }))
+}
+
+async fn edit(State(state): SharedState, Json(body): Json<Value>) -> Json<Value> {
+    if !authorized(body.get("token")) {
+        return failure("Invalid token");
+    }
+    let mut state = state.lock().unwrap();
+    let destination = body["destination_fld_id"].as_u64().map(|d| d as usize);
+    let new_name = body["new_name"].as_str().map(String::from);
+
+    if let (Some(codes), Some(destination)) = (body["file_codes"].as_str(), destination) {
+        let codes: Vec<&str> = codes.split(',').collect();
+        for file in state
+            .files
+            .iter_mut()
+            .filter(|f| codes.contains(&f.code.as_str()))
+        {
+            file.folder = destination;
+        }
+    } else if let (Some(code), Some(name)) = (body["file_code"].as_str(), new_name.clone()) {
+        for file in state.files.iter_mut().filter(|f| f.code == code) {
+            file.name = name.clone();
+        }
+    } else if let Some(id) = body["fld_id"].as_u64().map(|i| i as usize) {
+        for folder in state.folders.iter_mut().filter(|f| f.id == id) {
+            if let Some(destination) = destination {
+                folder.parent = Some(destination);
+            }
+            if let Some(name) = new_name.clone() {
+                folder.name = name;
+            }
+        }
+    } else {
+        return failure("Invalid parameters");
+    }
+    success(json!({ "updated": 1 }))
+}
+
+async fn create_folder(State(state): SharedState, Json(body): Json<Value>) -> Json<Value> {
+    if !authorized(body.get("token")) {
+        return failure("Invalid token");
+    }
+    let mut state = state.lock().unwrap();
+    let parent = body["path"].as_str().and_then(|p| state.folder(p));
+    let name = body["name"].as_str().map(String::from);
+    match (parent, name) {
+        (Some(parent), Some(name)) => {
+            let id = state.next_id();
+            state.folders.push(MockFolder {
+                id,
+                parent: Some(parent),
+                name,
+            });
+            success(json!({}))
+        }
+        _ => failure("Folder not found"),
+    }
+}
+
+async fn delete(State(state): SharedState, Json(body): Json<Value>) -> Json<Value> {
+    if !authorized(body.get("token")) {
+        return failure("Invalid token");
+    }
+    let mut state = state.lock().unwrap();
+    if let Some(codes) = body["file_codes"].as_str() {
+        let codes: Vec<String> = codes.split(',').map(String::from).collect();
+        state.files.retain(|f| !codes.contains(&f.code));
+    } else if let Some(id) = body["fld_id"].as_u64().map(|i| i as usize) {
+        let path = state.path(id);
+        let removed: Vec<usize> = state
+            .folders
+            .iter()
+            .map(|f| f.id)
+            .filter(|f| {
+                let folder = state.path(*f);
+                folder == path || folder.starts_with(&format!("{path}/"))
+            })
+            .collect();
+        state.folders.retain(|f| !removed.contains(&f.id));
+        state.files.retain(|f| !removed.contains(&f.folder));
+    } else {
+        return failure("Invalid parameters");
+    }
+    success(json!({}))
+}
+
+/// Serve a file with Range support
+async fn download(
+    State(state): SharedState,
+    Path(code): Path<String>,
+    headers: HeaderMap,
+) -> Response {
+    let state = state.lock().unwrap();
+    let Some(file) = state.files.iter().find(|f| f.code == code) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let range = headers
+        .get(header::RANGE)
+        .and_then(|r| r.to_str().ok())
+        .and_then(|r| r.strip_prefix("bytes="))
+        .and_then(|r| r.split_once('-'))
+        .and_then(|(start, end)| {
+            let start: usize = start.parse().ok()?;
+            let end: usize = end.parse().unwrap_or(file.data.len() - 1);
+            Some((start, end.min(file.data.len() - 1)))
+        });
+
+    match range {
+        Some((start, end)) => (
+            StatusCode::PARTIAL_CONTENT,
+            [(
+                header::CONTENT_RANGE,
+                format!("bytes {start}-{end}/{}", file.data.len()),
+            )],
+            file.data[start..=end].to_vec(),
+        )
+            .into_response(),
+        None => file.data.clone().into_response(),
+    }
+}
+
+/// Store an uploaded file in the root folder
+async fn upload(State(state): SharedState, mut multipart: Multipart) -> Response {
+    let mut uploaded = vec![];
+    while let Ok(Some(field)) = multipart.next_field().await {
+        let name = field.file_name().unwrap_or_default().to_string();
+        let data: Bytes = match field.bytes().await {
+            Ok(data) => data,
+            Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+        };
+
+        let mut state = state.lock().unwrap();
+        let code = format!("mock{:08}", state.next_id());
+        uploaded.push(json!({ "name": name, "size": data.len(), "url": code }));
+        state.files.push(MockFile {
+            code,
+            name,
+            folder: 0,
+            data: data.to_vec(),
+        });
+    }
+    Json(json!({ "files": uploaded })).into_response()
+}
+
+/// WebDAV share of a temporary directory, served under '/dav'
+pub struct DavServer {
+    pub dir: TempDir,
+    addr: SocketAddr,
+}
+
+impl DavServer {
+    pub async fn start() -> Self {
+        Self::serve(None).await
+    }
+
+    /// Share refusing the requests without the 'user' and 'secret' credentials
+    pub async fn start_with_login() -> Self {
+        Self::serve(Some("Basic dXNlcjpzZWNyZXQ=")).await
+    }
+
+    async fn serve(authorization: Option<&'static str>) -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let dav = DavHandler::builder()
+            .filesystem(LocalFs::new(dir.path(), false, false, false))
+            .locksystem(FakeLs::new())
+            .strip_prefix("/dav")
+            .build_handler();
+        let app = Router::new().fallback(move |req: Request| {
+            let dav = dav.clone();
+            async move {
+                let given = req.headers().get(header::AUTHORIZATION);
+                match authorization {
+                    Some(expected) if given.and_then(|a| a.to_str().ok()) != Some(expected) => {
+                        StatusCode::UNAUTHORIZED.into_response()
+                    }
+                    _ => dav.handle(req).await.into_response(),
+                }
+            }
+        });
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        Self { dir, addr }
+    }
+
+    /// Base url of the share
+    pub fn url(&self) -> String {
+        format!("http://{}/dav", self.addr)
+    }
+}
+
+/// Isolated configuration, data directory and fake mkvtoolnix
+pub struct TestEnv {
+    pub dir: TempDir,
+}
+
+impl TestEnv {
+    pub fn new(mock: &MockServer, destination: &str) -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+
+        let config = root.join("config/downtobox");
+        fs::create_dir_all(&config).unwrap();
+        fs::write(
+            config.join("config.toml"),
+            format!(
+                "local_path = \"{}\"\ndestination_path = \"{destination}\"\napi_key = \"{TOKEN}\"\napi_url = \"{}\"\nverify_sample = 1\ndisk_margin = 0\n",
+                root.join("local").display(),
+                mock.url()
+            ),
+        )
+        .unwrap();
+
+        let bin = root.join("bin");
+        fs::create_dir_all(&bin).unwrap();
+        Self::script(
+            &bin.join("mkvmerge"),
+            &format!("cat <<'EOF'\n{MKVMERGE_TRACKS}\nEOF"),
+        );
+        Self::script(&bin.join("mkvpropedit"), "exit 0");
+
+        Self { dir }
+    }
+
+    /// Append settings to the configuration
+    pub fn configure(&self, toml: &str) {
+        let path = self.dir.path().join("config/downtobox/config.toml");
+        let config = fs::read_to_string(&path).unwrap();
+        fs::write(path, format!("{config}{toml}\n")).unwrap();
+    }
+
+    /// Directory of the downloaded files
+    pub fn local(&self) -> PathBuf {
+        self.dir.path().join("local")
+    }
+
+    /// Run downtobox in the environment
+    pub async fn run(&self, args: &[&str]) -> Output {
+        self.command(args).output().await.unwrap()
+    }
+
+    /// Run downtobox, failing with its errors when it does not succeed
+    pub async fn run_ok(&self, args: &[&str]) -> Output {
+        let output = self.run(args).await;
+        assert_success(&output);
+        output
+    }
+
+    /// Run downtobox with additional environment variables
+    pub async fn run_with_env(&self, envs: &[(&str, &str)], args: &[&str]) -> Output {
+        self.command(args)
+            .envs(envs.iter().copied())
+            .output()
+            .await
+            .unwrap()
+    }
+
+    /// Run downtobox answering its questions with an input
+    pub async fn run_with_input(&self, input: &str, args: &[&str]) -> Output {
+        self.run_with_input_and_env(input, &[], args).await
+    }
+
+    /// Run downtobox with an input and additional environment variables
+    pub async fn run_with_input_and_env(
+        &self,
+        input: &str,
+        envs: &[(&str, &str)],
+        args: &[&str],
+    ) -> Output {
+        let mut child = self
+            .command(args)
+            .envs(envs.iter().copied())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut stdin = child.stdin.take().unwrap();
+        stdin.write_all(input.as_bytes()).await.unwrap();
+        drop(stdin);
+        child.wait_with_output().await.unwrap()
+    }
+
+    /// Start downtobox in the background, it is killed when dropped
+    pub fn spawn(&self, args: &[&str]) -> Child {
+        self.command(args)
+            .stdout(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .unwrap()
+    }
+
+    fn command(&self, args: &[&str]) -> Command {
+        let root = self.dir.path();
+        let path = format!(
+            "{}:{}",
+            root.join("bin").display(),
+            std::env::var("PATH").unwrap_or_default()
+        );
+        let mut command = Command::new(env!("CARGO_BIN_EXE_downtobox"));
+        command
+            .args(args)
+            .env("HOME", root)
+            .env("XDG_CONFIG_HOME", root.join("config"))
+            .env("XDG_DATA_HOME", root.join("data"))
+            .env("PATH", path);
+        command
+    }
+
+    fn script(path: &PathBuf, body: &str) {
+        fs::write(path, format!("#!/bin/sh\n{body}\n")).unwrap();
+        fs::set_permissions(path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+}
+
+/// Free local port
+pub fn free_port() -> u16 {
+    std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+/// Content of a test file
+pub fn content(size: usize) -> Vec<u8> {
+    (0..size).map(|i| (i % 251) as u8).collect()
+}
+
+/// Fail with the errors of a run when it does not succeed
+#[track_caller]
+pub fn assert_success(output: &Output) {
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
@@ -0,0 +1,308 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use common::{assert_success, content, DavServer, MockServer, TestEnv, TOKEN};
+use std::fs;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn profile_overrides_default_settings() {
+    let mock = MockServer::start().await;
+    mock.add_folder("//Films");
+    mock.add_folder("//Archive");
+    let env = TestEnv::new(&mock, "//Films");
+    env.configure("[profiles.archive]\ndestination_path = \"//Archive\"");
+    let path = env.dir.path().join("film.mkv");
+    fs::write(&path, content(1024 * 1024)).unwrap();
+
+    env.run_ok(&[
+        "upload",
+        "film",
+        "My Film",
+        &path.to_string_lossy(),
+        "--profile",
+        "archive",
+    ])
+    .await;
+    assert_eq!(mock.files_in("//Archive").len(), 1);
+    assert!(mock.files_in("//Films").is_empty());
+
+    // The profiles are listed with their resolved settings
+    let output = env.run(&["config", "profiles", "-P", "archive"]).await;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("* archive: //Archive (uptobox)"),
+        "{stdout}"
+    );
+
+    // Unknown profiles are refused
+    let output = env.run(&["account", "--profile", "missing"]).await;
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("available: archive"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn profile_sections_inherit_the_default_keys() {
+    let dav = DavServer::start_with_login().await;
+    fs::write(dav.dir.path().join("a.mkv"), content(1024)).unwrap();
+    let mock = MockServer::start().await;
+    let env = TestEnv::new(&mock, "//");
+    env.configure(&format!(
+        "[storage]\nbackend = \"webdav\"\nurl = \"{}\"\nusername = \"user\"\npassword = \"wrong\"\n[profiles.login.storage]\npassword = \"secret\"",
+        dav.url()
+    ));
+
+    let output = env.run(&["remote", "ls", "//"]).await;
+    assert!(!output.status.success());
+
+    // The url and the username of the default section are kept
+    let output = env.run_ok(&["remote", "ls", "//", "-P", "login"]).await;
+    assert!(String::from_utf8_lossy(&output.stdout).contains("a.mkv"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn command_line_overrides_environment_and_profile() {
+    let mock = MockServer::start().await;
+    mock.add_folder("//Films");
+    mock.add_folder("//Env");
+    mock.add_folder("//Cli");
+    let env = TestEnv::new(&mock, "//Films");
+    env.configure("[profiles.archive]\ndestination_path = \"//Archive\"");
+    let path = env.dir.path().join("film.mkv");
+    fs::write(&path, content(1024 * 1024)).unwrap();
+    let path = path.to_string_lossy();
+
+    // The environment replaces the profile
+    let output = env
+        .run_with_env(
+            &[("DOWNTOBOX_DESTINATION_PATH", "//Env")],
+            &["upload", "film", "My Film", &path, "-P", "archive"],
+        )
+        .await;
+    assert_success(&output);
+    assert_eq!(mock.files_in("//Env").len(), 1);
+
+    // The command line replaces the environment
+    let output = env
+        .run_with_env(
+            &[("DOWNTOBOX_DESTINATION_PATH", "//Env")],
+            &[
+                "upload",
+                "film",
+                "My Film",
+                &path,
+                "--destination-path",
+                "//Cli",
+            ],
+        )
+        .await;
+    assert_success(&output);
+    assert_eq!(mock.files_in("//Cli").len(), 1);
+    assert_eq!(mock.files_in("//Env").len(), 1);
+
+    // Typed settings are checked
+    let output = env.run(&["account", "--disk-margin", "lots"]).await;
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Unable to parse 'disk_margin'"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn config_option_loads_another_file() {
+    let mock = MockServer::start().await;
+    mock.add_folder("//Films");
+    mock.add_folder("//Other");
+    let env = TestEnv::new(&mock, "//Films");
+    let default = env.dir.path().join("config/downtobox/config.toml");
+    let other = env.dir.path().join("other.toml");
+    let toml = fs::read_to_string(default)
+        .unwrap()
+        .replace("//Films", "//Other");
+    fs::write(&other, toml).unwrap();
+    let path = env.dir.path().join("film.mkv");
+    fs::write(&path, content(1024 * 1024)).unwrap();
+
+    env.run_ok(&[
+        "upload",
+        "film",
+        "My Film",
+        &path.to_string_lossy(),
+        "--config",
+        &other.to_string_lossy(),
+    ])
+    .await;
+    assert_eq!(mock.files_in("//Other").len(), 1);
+
+    // A missing file is not created
+    let output = env.run(&["account", "--config", "/nonexistent.toml"]).await;
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("/nonexistent.toml"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn config_check_reports_every_problem() {
+    let mock = MockServer::start().await;
+    mock.add_folder("//Films");
+    let env = TestEnv::new(&mock, "//Films");
+
+    let output = env.run_ok(&["config", "check"]).await;
+    assert!(String::from_utf8_lossy(&output.stdout).contains("The config is valid"));
+
+    // A file in place of the local directory, a wrong key and an invalid limit
+    let file = env.dir.path().join("file");
+    fs::write(&file, "").unwrap();
+    env.configure("[limits]\ndownload = \"lots\"");
+    let output = env
+        .run(&[
+            "config",
+            "check",
+            "--api-key",
+            "wrong",
+            "--local-path",
+            &file.join("local").to_string_lossy(),
+        ])
+        .await;
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.lines().filter(|l| l.starts_with("- ")).count(), 3);
+    assert!(stdout.contains("Invalid token"), "{stdout}");
+    assert!(stdout.contains("is not a directory"), "{stdout}");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("3 problem(s)"));
+
+    // A missing secret and an unknown profile are reported with the others
+    let secret = env.dir.path().join("missing.key");
+    let output = env
+        .run(&[
+            "config",
+            "check",
+            "--profile",
+            "missing",
+            "--api-key",
+            &format!("file:{}", secret.display()),
+            "--local-path",
+            &file.join("local").to_string_lossy(),
+        ])
+        .await;
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.lines().filter(|l| l.starts_with("- ")).count(), 4);
+    assert!(
+        stdout.contains("The profile 'missing' does not exist"),
+        "{stdout}"
+    );
+    assert!(stdout.contains("Unable to read the secret"), "{stdout}");
+    assert!(stdout.contains("is not a directory"), "{stdout}");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("4 problem(s)"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn config_init_verifies_the_answers() {
+    let mock = MockServer::start().await;
+    mock.add_folder("//Films");
+    let env = TestEnv::new(&mock, "//Films");
+    let path = env.dir.path().join("new/config.toml");
+    let path = path.to_string_lossy();
+    let url = mock.url();
+    fs::write(env.dir.path().join("file"), "").unwrap();
+
+    // A wrong key, a file in place of the local directory and a missing destination are asked
+    // again, the local directory is checked in the home directory
+    let input = format!("wrong\n{TOKEN}\n~/file/local\n~/downloads\n//Missing\n//Films\n");
+    let output = env
+        .run_with_input(
+            &input,
+            &["config", "init", "--config", &path, "--api-url", &url],
+        )
+        .await;
+    assert_success(&output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Invalid token"), "{stderr}");
+    assert!(stderr.contains("is not a directory"), "{stderr}");
+    assert!(stderr.contains("'//Missing' does not exist"), "{stderr}");
+
+    let toml = fs::read_to_string(path.as_ref()).unwrap();
+    assert!(toml.contains(&format!("api_key = \"{TOKEN}\"")), "{toml}");
+    assert!(toml.contains("local_path = \"~/downloads\""), "{toml}");
+    assert!(toml.contains("destination_path = \"//Films\""), "{toml}");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn api_key_read_from_secret_files() {
+    let mock = MockServer::start().await;
+    let env = TestEnv::new(&mock, "//");
+
+    // Plain secret files, as mounted by containers
+    let plain = env.dir.path().join("uptobox");
+    fs::write(&plain, format!("{TOKEN}\n")).unwrap();
+    let reference = format!("file:{}", plain.display());
+    env.run_ok(&["account", "--api-key", &reference]).await;
+
+    // Encrypted secret files referenced by the config
+    let encrypted = env.dir.path().join("secrets/api_key");
+    let passphrase = [("DOWNTOBOX_PASSPHRASE", "hunter2")];
+    let output = env
+        .run_with_input_and_env(
+            &format!("{TOKEN}\n"),
+            &passphrase,
+            &["config", "set-key", "--file", &encrypted.to_string_lossy()],
+        )
+        .await;
+    assert_success(&output);
+    assert!(!fs::read_to_string(&encrypted)
+        .unwrap_or_default()
+        .contains(TOKEN));
+    let config = fs::read_to_string(env.dir.path().join("config/downtobox/config.toml")).unwrap();
+    assert!(!config.contains(TOKEN), "{config}");
+
+    let output = env.run_with_env(&passphrase, &["account"]).await;
+    assert_success(&output);
+    let output = env
+        .run_with_env(&[("DOWNTOBOX_PASSPHRASE", "wrong")], &["account"])
+        .await;
+    assert!(String::from_utf8_lossy(&output.stderr).contains("wrong passphrase"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn config_init_keeps_the_key_reference() {
+    let mock = MockServer::start().await;
+    mock.add_folder("//Films");
+    let env = TestEnv::new(&mock, "//Films");
+    env.configure("[profiles.other]\ndestination_path = \"//Other\"");
+    let config = env.dir.path().join("config/downtobox/config.toml");
+    let encrypted = env.dir.path().join("secrets/api_key");
+    let reference = format!("file:{}", encrypted.display());
+    let passphrase = ("DOWNTOBOX_PASSPHRASE", "hunter2");
+
+    let output = env
+        .run_with_input_and_env(
+            &format!("{TOKEN}\n"),
+            &[passphrase],
+            &["config", "set-key", "--file", &encrypted.to_string_lossy()],
+        )
+        .await;
+    assert_success(&output);
+
+    // The reference is the default answer, the key of the environment is not written
+    let local = env.local();
+    let output = env
+        .run_with_input_and_env(
+            &format!("\n{}\n\n", local.display()),
+            &[passphrase, ("DOWNTOBOX_API_KEY", "from-the-environment")],
+            &["config", "init"],
+        )
+        .await;
+    assert_success(&output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains(&format!("[{reference}]")), "{stdout}");
+    assert!(!stdout.contains(TOKEN), "{stdout}");
+    let toml = fs::read_to_string(&config).unwrap();
+    assert!(
+        toml.contains(&format!("api_key = \"{reference}\"")),
+        "{toml}"
+    );
+    assert!(!toml.contains(TOKEN), "{toml}");
+    assert!(!toml.contains("from-the-environment"), "{toml}");
+
+    // The profiles are listed without the passphrase
+    let output = env.run_ok(&["config", "profiles"]).await;
+    assert!(String::from_utf8_lossy(&output.stdout).contains("other: //Other"));
+}
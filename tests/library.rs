@@ -0,0 +1,78 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use common::{content, MockServer, TestEnv, TOKEN};
+use downtobox::{Config, Event, Limiters, Pipeline, UploadType, UptoboxApi};
+use reqwest::Client;
+use std::{env, fs, sync::Mutex};
+
+#[tokio::test(flavor = "multi_thread")]
+async fn pipeline_reports_progress_events() {
+    let mock = MockServer::start().await;
+    mock.add_file(
+        "abcdefghijkl",
+        "some.film.mkv",
+        "//",
+        content(2 * 1024 * 1024),
+    );
+    mock.add_folder("//Films");
+    let env = TestEnv::new(&mock, "//Films");
+
+    // The pipeline runs in this process, next to the fake mkvtoolnix
+    let root = env.dir.path();
+    env::set_var("XDG_DATA_HOME", root.join("data"));
+    env::set_var(
+        "PATH",
+        format!(
+            "{}:{}",
+            root.join("bin").display(),
+            env::var("PATH").unwrap_or_default()
+        ),
+    );
+    fs::create_dir_all(env.local()).unwrap();
+
+    let config = Config {
+        local_path: env.local().to_string_lossy().to_string(),
+        destination_path: String::from("//Films"),
+        api_key: String::from(TOKEN),
+        api_url: mock.url(),
+        disk_margin: 0,
+        ..Config::default()
+    };
+    let client = Client::new();
+    let uptobox = UptoboxApi::new(client.clone(), &config.api_key, &config.api_url);
+    let limiters = Limiters::new(&config.limits, &None, &None).unwrap();
+
+    let events = Mutex::new(vec![]);
+    let pipeline = Pipeline::new(
+        (&uptobox, &client, &config, &limiters),
+        UploadType::Film,
+        "My Film",
+    )
+    .sources("web")
+    .on_event(|event| {
+        let name = match event {
+            Event::Download { .. } => "download",
+            Event::Progress { .. } => return,
+            Event::Downloaded { .. } => "downloaded",
+            Event::Upload { .. } => "upload",
+            Event::Verified { .. } => "verified",
+            Event::Done { .. } => "done",
+        };
+        events.lock().unwrap().push(name);
+    });
+    pipeline
+        .backup("https://uptobox.com/abcdefghijkl")
+        .await
+        .unwrap();
+    drop(pipeline);
+
+    assert_eq!(
+        events.into_inner().unwrap(),
+        ["download", "downloaded", "upload", "verified", "done"]
+    );
+    let uploaded = mock.files_in("//Films");
+    assert_eq!(uploaded.len(), 1);
+    assert_eq!(uploaded[0].name, "My.Film.1080p.WEBDL.EAC3.h264.mkv");
+}
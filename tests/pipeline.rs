@@ -0,0 +1,506 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use common::{assert_success, content, MockServer, TestEnv};
+use std::fs;
+
+const LINK: &str = "https://uptobox.com/abcdefghijkl";
+
+#[tokio::test(flavor = "multi_thread")]
+async fn backup_show_downloads_renames_and_moves() {
+    let mock = MockServer::start().await;
+    let data = content(3 * 1024 * 1024);
+    mock.add_file("abcdefghijkl", "some.show.s01e02.mkv", "//", data.clone());
+    mock.add_folder("//Shows/My Show");
+    let env = TestEnv::new(&mock, "//Shows/My Show");
+
+    env.run_ok(&[
+        "backup", "show", "My Show", LINK, "-l", "multi", "-s", "web",
+    ])
+    .await;
+
+    // The file is downloaded without leaving a partial file
+    let local = env.local().join("some.show.s01e02.mkv");
+    assert_eq!(fs::read(&local).unwrap(), data);
+    assert!(!env.local().join("some.show.s01e02.mkv.part").exists());
+
+    // The renamed upload is moved to the destination
+    let uploaded = mock.files_in("//Shows/My Show");
+    assert_eq!(uploaded.len(), 1);
+    assert_eq!(
+        uploaded[0].name,
+        "My.Show.s01e02.MULTi.1080p.WEBDL.EAC3.h264.mkv"
+    );
+    assert_eq!(uploaded[0].data, data);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn upload_film_moves_to_destination() {
+    let mock = MockServer::start().await;
+    mock.add_folder("//Films");
+    let env = TestEnv::new(&mock, "//Films");
+    let path = env.dir.path().join("film.mkv");
+    fs::write(&path, content(1024 * 1024)).unwrap();
+
+    env.run_ok(&["upload", "film", "My Film", &path.to_string_lossy()])
+        .await;
+
+    let uploaded = mock.files_in("//Films");
+    assert_eq!(uploaded.len(), 1);
+    assert_eq!(uploaded[0].name, "My.Film.1080p.EAC3.h264.mkv");
+    assert!(mock.files_in("//").is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn upload_refuses_partial_downloads() {
+    let mock = MockServer::start().await;
+    mock.add_folder("//Films");
+    let env = TestEnv::new(&mock, "//Films");
+    let path = env.dir.path().join("film.mkv.part");
+    fs::write(&path, content(1024)).unwrap();
+
+    let output = env
+        .run(&["upload", "film", "My Film", &path.to_string_lossy()])
+        .await;
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("incomplete download"));
+    assert!(mock.files_in("//").is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn backup_requires_premium() {
+    let mock = MockServer::start().await;
+    mock.add_file("abcdefghijkl", "some.show.s01e02.mkv", "//", content(1024));
+    mock.state().premium = false;
+    let env = TestEnv::new(&mock, "//");
+
+    let output = env.run(&["backup", "show", "My Show", LINK]).await;
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("premium account"));
+    assert!(!env.local().join("some.show.s01e02.mkv").exists());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn backup_defers_downloads_without_space() {
+    let mock = MockServer::start().await;
+    mock.add_file("abcdefghijkl", "some.show.s01e02.mkv", "//", content(1024));
+    mock.add_folder("//Shows");
+    let env = TestEnv::new(&mock, "//Shows");
+
+    // No space is left once the margin is kept
+    let output = env
+        .run(&[
+            "backup",
+            "show",
+            "My Show",
+            LINK,
+            "--disk-margin",
+            "1000000000000",
+        ])
+        .await;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("1 download(s) deferred"), "{stderr}");
+    assert!(stderr.contains(&format!("  {LINK} (1.00 KiB)")), "{stderr}");
+    assert!(!env.local().join("some.show.s01e02.mkv").exists());
+    assert!(mock.files_in("//Shows").is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn upload_uses_configured_aliases() {
+    let mock = MockServer::start().await;
+    mock.add_folder("//Films");
+    let env = TestEnv::new(&mock, "//Films");
+    let path = env.dir.path().join("film.mkv");
+    fs::write(&path, content(1024 * 1024)).unwrap();
+    let path = path.to_string_lossy();
+
+    // Unknown tokens are refused instead of being dropped from the title
+    let output = env
+        .run(&["upload", "film", "My Film", &path, "-s", "hdtv"])
+        .await;
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Unknown source 'hdtv'"));
+    assert!(mock.files_in("//").is_empty());
+
+    env.configure(
+        "[aliases.sources]\nHDTV = [\"hdtv\"]\n[aliases.languages]\nTRUEFRENCH = [\"truefrench\", \"tf\"]",
+    );
+    env.run_ok(&[
+        "upload", "film", "My Film", &path, "-s", "hdtv web", "-l", "tf",
+    ])
+    .await;
+    let uploaded = mock.files_in("//Films");
+    assert_eq!(
+        uploaded[0].name,
+        "My.Film.TRUEFRENCH.1080p.HDTV.WEBDL.EAC3.h264.mkv"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn titles_include_services_and_editions() {
+    let mock = MockServer::start().await;
+    let data = content(1024 * 1024);
+    mock.add_file(
+        "abcdefghijkl",
+        "Some.Show.S01E02.REPACK.1080p.AMZN.WEB-DL-GRP.mkv",
+        "//",
+        data,
+    );
+    mock.add_folder("//Shows");
+    mock.add_folder("//Films");
+    let env = TestEnv::new(&mock, "//Shows");
+
+    // Detected from the original file name
+    env.run_ok(&[
+        "backup", "show", "My Show", LINK, "-l", "multi", "-s", "web",
+    ])
+    .await;
+    assert_eq!(
+        mock.files_in("//Shows")[0].name,
+        "My.Show.S01E02.REPACK.MULTi.1080p.AMZN.WEBDL.EAC3.h264-GRP.mkv"
+    );
+
+    // Given on the command line
+    let path = env.dir.path().join("film.mkv");
+    fs::write(&path, content(1024)).unwrap();
+    env.run_ok(&[
+        "upload",
+        "film",
+        "My Film",
+        &path.to_string_lossy(),
+        "-s",
+        "web",
+        "--services",
+        "netflix",
+        "--editions",
+        "extended imax",
+        "--destination-path",
+        "//Films",
+    ])
+    .await;
+    assert_eq!(
+        mock.files_in("//Films")[0].name,
+        "My.Film.EXTENDED.IMAX.1080p.NF.WEBDL.EAC3.h264.mkv"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn release_group_kept_overridden_or_dropped() {
+    let mock = MockServer::start().await;
+    mock.add_folder("//Films");
+    let env = TestEnv::new(&mock, "//Films");
+    let mut names = vec![];
+    for (title, file, args) in [
+        ("Kept", "Film.2023.WEB-DL-GRP.mkv", vec![]),
+        ("Source", "Film.2023.WEB-DL.mkv", vec![]),
+        ("Overridden", "Film.2023-GRP.mkv", vec!["--group", "MINE"]),
+        ("Dropped", "Film.2024-GRP.mkv", vec!["--no-group"]),
+    ] {
+        let path = env.dir.path().join(file);
+        fs::write(&path, content(1024)).unwrap();
+        let path = path.to_string_lossy();
+        let mut command = vec!["upload", "film", title, &path];
+        command.extend(args);
+        env.run_ok(&command).await;
+        names.extend(mock.files_in("//Films").into_iter().map(|f| f.name));
+    }
+    names.sort();
+    names.dedup();
+    assert_eq!(
+        names,
+        [
+            "Dropped.1080p.EAC3.h264.mkv",
+            "Kept.1080p.EAC3.h264-GRP.mkv",
+            "Overridden.1080p.EAC3.h264-MINE.mkv",
+            "Source.1080p.EAC3.h264.mkv",
+        ]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn titles_are_sanitised_and_truncated() {
+    let mock = MockServer::start().await;
+    mock.add_folder("//Films");
+    let env = TestEnv::new(&mock, "//Films");
+    let path = env.dir.path().join("film.mkv");
+    fs::write(&path, content(1024)).unwrap();
+    let path = path.to_string_lossy();
+
+    env.run_ok(&[
+        "upload",
+        "film",
+        "Amélie: Le Destin d'Amélie / Poulain - 🎬",
+        &path,
+    ])
+    .await;
+
+    // The technical suffix is kept whole
+    env.run_ok(&[
+        "upload",
+        "film",
+        "A Very Long Title That Keeps Going",
+        &path,
+        "--max-name-length",
+        "30",
+    ])
+    .await;
+
+    let mut names: Vec<String> = mock
+        .files_in("//Films")
+        .into_iter()
+        .map(|f| f.name)
+        .collect();
+    names.sort();
+    assert_eq!(
+        names,
+        [
+            "A.Very.Lon.1080p.EAC3.h264.mkv",
+            "Amelie.Le.Destin.dAmelie.Poulain.1080p.EAC3.h264.mkv",
+        ]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn suffix_applied_to_title_and_file_name() {
+    let mock = MockServer::start().await;
+    mock.add_folder("//Films");
+    let env = TestEnv::new(&mock, "//Films");
+    let log = env.dir.path().join("mkvpropedit.log");
+    fs::write(
+        env.dir.path().join("bin/mkvpropedit"),
+        format!(
+            "#!/bin/sh\n[ -n \"$5\" ] && echo \"$5\" >> '{}'\nexit 0\n",
+            log.display()
+        ),
+    )
+    .unwrap();
+    env.configure("[suffix]\nmode = \"hash\"\nfile_name = true");
+    let data = content(1024);
+    for name in ["first.mkv", "second.mkv"] {
+        let path = env.dir.path().join(name);
+        fs::write(&path, &data).unwrap();
+        env.run_ok(&["upload", "film", "My Film", &path.to_string_lossy()])
+            .await;
+    }
+
+    // The same content gives the same suffix in the segment title and the file name
+    let uploaded = mock.files_in("//Films");
+    assert_eq!(uploaded.len(), 2);
+    assert_eq!(uploaded[0].name, uploaded[1].name);
+    let suffix = uploaded[0]
+        .name
+        .trim_end_matches(".mkv")
+        .rsplit('.')
+        .next()
+        .unwrap()
+        .to_string();
+    assert_eq!(suffix.len(), 7);
+    let titles = fs::read_to_string(&log).unwrap();
+    assert!(
+        titles
+            .lines()
+            .all(|t| t == format!("title=My.Film.1080p.EAC3.h264.mkv - {suffix}")),
+        "{titles}"
+    );
+
+    // No suffix at all
+    let path = env.dir.path().join("third.mkv");
+    fs::write(&path, &data).unwrap();
+    env.run_ok(&[
+        "upload",
+        "film",
+        "Other Film",
+        &path.to_string_lossy(),
+        "--suffix-mode",
+        "none",
+    ])
+    .await;
+    let titles = fs::read_to_string(&log).unwrap();
+    assert_eq!(
+        titles.lines().last(),
+        Some("title=Other.Film.1080p.EAC3.h264.mkv")
+    );
+    assert!(mock
+        .files_in("//Films")
+        .iter()
+        .any(|f| f.name == "Other.Film.1080p.EAC3.h264.mkv"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn sync_delete_keeps_the_files_of_other_titles() {
+    let mock = MockServer::start().await;
+    let destination = "//Library";
+    mock.add_file(
+        "extra0000001",
+        "My.Show.s01e01.1080p.EAC3.h264.mkv",
+        destination,
+        content(1024),
+    );
+    mock.add_file(
+        "other0000001",
+        "Other.Show.s01e01.1080p.EAC3.h264.mkv",
+        destination,
+        content(1024),
+    );
+    mock.add_file(
+        "film00000001",
+        "My.Film.1080p.EAC3.h264.mkv",
+        destination,
+        content(1024),
+    );
+    // A title starting with the synced one
+    mock.add_file(
+        "usshow000001",
+        "My.Show.US.s01e01.1080p.EAC3.h264.mkv",
+        destination,
+        content(1024),
+    );
+    let env = TestEnv::new(&mock, destination);
+    let dir = env.dir.path().join("My Show");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("some.show.s01e02.mkv"), content(1024)).unwrap();
+
+    let output = env
+        .run_ok(&[
+            "sync",
+            "show",
+            "My Show",
+            &dir.to_string_lossy(),
+            "--delete",
+            "-y",
+        ])
+        .await;
+
+    // The preview lists the scope of the deletions
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("Files of 'My Show' in '//Library' missing"),
+        "{stdout}"
+    );
+    assert!(
+        stdout.contains("delete  My.Show.s01e01.1080p.EAC3.h264.mkv"),
+        "{stdout}"
+    );
+    assert!(!stdout.contains("delete  Other.Show"), "{stdout}");
+    assert!(!stdout.contains("delete  My.Show.US"), "{stdout}");
+
+    let names: Vec<String> = mock
+        .files_in(destination)
+        .into_iter()
+        .map(|f| f.name)
+        .collect();
+    assert_eq!(names.len(), 4, "{names:?}");
+    assert!(names.contains(&String::from("Other.Show.s01e01.1080p.EAC3.h264.mkv")));
+    assert!(names.contains(&String::from("My.Show.US.s01e01.1080p.EAC3.h264.mkv")));
+    assert!(names.contains(&String::from("My.Film.1080p.EAC3.h264.mkv")));
+    assert!(names.contains(&String::from("My.Show.s01e02.1080p.EAC3.h264.mkv")));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn retitle_renames_without_leaving_the_head() {
+    let mock = MockServer::start().await;
+    mock.add_file(
+        "abcdefghijkl",
+        "some.show.s01e02.mkv",
+        "//Shows",
+        content(1024),
+    );
+    let env = TestEnv::new(&mock, "//Shows");
+    let tmp = env.dir.path().join("tmp");
+    fs::create_dir_all(&tmp).unwrap();
+    let tmp_env = [("TMPDIR", tmp.to_str().unwrap())];
+
+    let output = env
+        .run_with_env(&tmp_env, &["retitle", "show", "My Show", LINK])
+        .await;
+    assert_success(&output);
+    assert_eq!(
+        mock.files_in("//Shows")[0].name,
+        "My.Show.s01e02.1080p.EAC3.h264.mkv"
+    );
+    assert_eq!(fs::read_dir(&tmp).unwrap().count(), 0);
+
+    // The head is removed when the tracks cannot be read
+    fs::write(env.dir.path().join("bin/mkvmerge"), "#!/bin/sh\nexit 1\n").unwrap();
+    let output = env
+        .run_with_env(&tmp_env, &["retitle", "show", "Other Show", LINK])
+        .await;
+    assert!(!output.status.success());
+    assert_eq!(fs::read_dir(&tmp).unwrap().count(), 0);
+}
+
+#[tokio::test]
+async fn retitle_applies_the_file_name_suffix() {
+    let mock = MockServer::start().await;
+    mock.add_file(
+        "abcdefghijkl",
+        "some.show.s01e02.mkv",
+        "//Shows",
+        content(1024),
+    );
+    let env = TestEnv::new(&mock, "//Shows");
+    env.configure("[suffix]\nmode = \"tag\"\ntag = \"mine\"\nfile_name = true");
+
+    env.run_ok(&["retitle", "show", "My Show", LINK]).await;
+    assert_eq!(
+        mock.files_in("//Shows")[0].name,
+        "My.Show.s01e02.1080p.EAC3.h264.mine.mkv"
+    );
+
+    // The suffixed file name is already titled
+    let output = env.run(&["retitle", "show", "My Show", LINK]).await;
+    assert!(String::from_utf8_lossy(&output.stdout).contains("is already titled"));
+    assert_eq!(
+        mock.files_in("//Shows")[0].name,
+        "My.Show.s01e02.1080p.EAC3.h264.mine.mkv"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn retitle_keeps_the_hash_suffix() {
+    let mock = MockServer::start().await;
+    mock.add_file(
+        "abcdefghijkl",
+        "Old.Show.s01e02.1080p.EAC3.h264.0a1b2c3.mkv",
+        "//Shows",
+        content(1024),
+    );
+    mock.add_file(
+        "nosuffix0001",
+        "some.show.s01e03.mkv",
+        "//Shows",
+        content(1024),
+    );
+    let env = TestEnv::new(&mock, "//Shows");
+    env.configure("[suffix]\nmode = \"hash\"\nfile_name = true");
+
+    // The hash of the whole file given at upload is kept
+    env.run_ok(&["retitle", "show", "My Show", LINK]).await;
+    let names: Vec<String> = mock
+        .files_in("//Shows")
+        .into_iter()
+        .map(|f| f.name)
+        .collect();
+    assert!(
+        names.contains(&String::from("My.Show.s01e02.1080p.EAC3.h264.0a1b2c3.mkv")),
+        "{names:?}"
+    );
+
+    // A hash suffix is not computed from the head
+    let output = env
+        .run(&[
+            "retitle",
+            "show",
+            "My Show",
+            "https://uptobox.com/nosuffix0001",
+        ])
+        .await;
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Unable to give a hash suffix"));
+    assert!(mock
+        .files_in("//Shows")
+        .iter()
+        .any(|f| f.name == "some.show.s01e03.mkv"));
+}
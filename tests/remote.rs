@@ -0,0 +1,220 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use common::{content, MockServer, TestEnv};
+use std::fs;
+
+fn names(mock: &MockServer, path: &str) -> Vec<String> {
+    let mut names: Vec<String> = mock.files_in(path).into_iter().map(|f| f.name).collect();
+    names.sort();
+    names
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn dedupe_deletes_only_the_copies() {
+    let mock = MockServer::start().await;
+    let name = "My.Show.S01E02.VFF.1080p.WEBDL.EAC3.h264";
+    mock.add_file(
+        "copy00000001",
+        &format!("{name}.aB3dE5f.mkv"),
+        "//Shows",
+        content(1024),
+    );
+    mock.add_file(
+        "copy00000002",
+        &format!("{name}.xY9zW8v.mkv"),
+        "//Shows/Old",
+        content(1024),
+    );
+    mock.add_file(
+        "size00000001",
+        &format!("{name}.kL4mN6p.mkv"),
+        "//Shows",
+        content(2048),
+    );
+    mock.add_file(
+        "lang00000001",
+        "My.Show.S01E02.VOSTFR.2160p.WEBDL.EAC3.h264.qR7sT1u.mkv",
+        "//Shows",
+        content(1024),
+    );
+    mock.add_file(
+        "tags00000001",
+        "My.Film.2023.1080p.UNRATED.mkv",
+        "//Shows",
+        content(1024),
+    );
+    mock.add_file(
+        "tags00000002",
+        "My.Film.2023.1080p.HDLight.mkv",
+        "//Shows",
+        content(1024),
+    );
+    let env = TestEnv::new(&mock, "//Shows");
+    env.configure("[suffix]\nmode = \"random\"\nfile_name = true");
+
+    // Nothing is deleted by default
+    let output = env.run(&["dedupe", "//Shows"]).await;
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("1 file(s) would be deleted"), "{stdout}");
+    assert_eq!(mock.state().files.len(), 6);
+
+    // Nor without a confirmation
+    let output = env
+        .run_with_input("n\n", &["dedupe", "//Shows", "--delete"])
+        .await;
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Aborted"));
+    assert_eq!(mock.state().files.len(), 6);
+
+    env.run_ok(&["dedupe", "//Shows", "--delete", "-y"]).await;
+    let codes: Vec<String> = mock.state().files.iter().map(|f| f.code.clone()).collect();
+    assert_eq!(codes.len(), 5, "{codes:?}");
+    assert!(codes.contains(&String::from("size00000001")));
+    assert!(codes.contains(&String::from("lang00000001")));
+    assert!(codes.contains(&String::from("tags00000001")));
+    assert!(codes.contains(&String::from("tags00000002")));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn remote_rm_and_mv() {
+    let mock = MockServer::start().await;
+    mock.add_file("file00000001", "a.mkv", "//Inbox", content(1024));
+    mock.add_file("file00000002", "b.mkv", "//Inbox", content(1024));
+    mock.add_file("file00000003", "c.mkv", "//Old/Season 1", content(1024));
+    mock.add_folder("//Films");
+    let env = TestEnv::new(&mock, "//Films");
+
+    env.run_ok(&["remote", "mv", "//Inbox/a.mkv", "//Films"])
+        .await;
+    assert_eq!(names(&mock, "//Films"), ["a.mkv"]);
+    assert_eq!(names(&mock, "//Inbox"), ["b.mkv"]);
+
+    // Missing files are reported without touching the others
+    let output = env
+        .run(&["remote", "rm", "//Inbox/b.mkv", "//Inbox/missing.mkv"])
+        .await;
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("'//Inbox/missing.mkv' was not found"));
+    assert_eq!(names(&mock, "//Inbox"), ["b.mkv"]);
+
+    let output = env.run(&["remote", "rm", "//Inbox/b.mkv"]).await;
+    assert!(output.status.success());
+    assert!(names(&mock, "//Inbox").is_empty());
+
+    // Folders are only deleted with --recursive
+    let output = env.run(&["remote", "rm", "//Old"]).await;
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("use --recursive"));
+    assert_eq!(names(&mock, "//Old/Season 1"), ["c.mkv"]);
+
+    let output = env.run(&["remote", "rm", "-r", "//Old"]).await;
+    assert!(output.status.success());
+    assert!(mock.state().files.iter().all(|f| f.name != "c.mkv"));
+    assert_eq!(names(&mock, "//Films"), ["a.mkv"]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn archive_policy_moves_the_downloads() {
+    let mock = MockServer::start().await;
+    mock.add_file("abcdefghijkl", "some.show.s01e02.mkv", "//", content(1024));
+    mock.add_folder("//Shows");
+    let env = TestEnv::new(&mock, "//Shows");
+    let archive = env.dir.path().join("archive");
+    env.configure(&format!(
+        "[local_policy]\nmode = \"archive\"\npath = \"{}\"",
+        archive.display()
+    ));
+
+    env.run_ok(&[
+        "backup",
+        "show",
+        "My Show",
+        "https://uptobox.com/abcdefghijkl",
+    ])
+    .await;
+    assert!(!env.local().join("some.show.s01e02.mkv").exists());
+    assert_eq!(
+        fs::read(archive.join("some.show.s01e02.mkv")).unwrap(),
+        content(1024)
+    );
+
+    // The job record follows the archived file
+    let archived = archive.join("some.show.s01e02.mkv");
+    let output = env.run(&["verify", &archived.to_string_lossy()]).await;
+    assert!(
+        String::from_utf8_lossy(&output.stdout).contains(": OK"),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn keep_recent_policy_deletes_the_oldest_uploads() {
+    let mock = MockServer::start().await;
+    mock.add_file("abcdefghijk1", "some.show.s01e01.mkv", "//", content(1024));
+    mock.add_file("abcdefghijk2", "some.show.s01e02.mkv", "//", content(2048));
+    mock.add_folder("//Shows");
+    let env = TestEnv::new(&mock, "//Shows");
+    env.configure("[local_policy]\nmode = \"keep_recent\"\ncount = 1");
+
+    // A local file that was never uploaded is kept
+    fs::create_dir_all(env.local()).unwrap();
+    fs::write(env.local().join("notes.mkv"), content(10)).unwrap();
+
+    for code in ["abcdefghijk1", "abcdefghijk2"] {
+        env.run_ok(&[
+            "backup",
+            "show",
+            "My Show",
+            &format!("https://uptobox.com/{code}"),
+        ])
+        .await;
+    }
+
+    assert!(!env.local().join("some.show.s01e01.mkv").exists());
+    assert!(env.local().join("some.show.s01e02.mkv").exists());
+    assert!(env.local().join("notes.mkv").exists());
+    assert_eq!(mock.files_in("//Shows").len(), 2);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn keep_recent_policy_keeps_the_failed_uploads() {
+    let mock = MockServer::start().await;
+    mock.add_file("abcdefghijk1", "some.show.s01e01.mkv", "//", content(1024));
+    mock.add_file("abcdefghijk2", "some.show.s01e02.mkv", "//", content(2048));
+    mock.add_folder("//Shows");
+    let env = TestEnv::new(&mock, "//Shows");
+    env.configure("[local_policy]\nmode = \"keep_recent\"\ncount = 1");
+
+    // The first title update fails once the file is downloaded
+    let mkvpropedit = env.dir.path().join("bin/mkvpropedit");
+    let original = fs::read(&mkvpropedit).unwrap();
+    fs::write(&mkvpropedit, "#!/bin/sh\n[ -n \"$5\" ] && exit 1\nexit 0\n").unwrap();
+    let output = env
+        .run(&[
+            "backup",
+            "show",
+            "My Show",
+            "https://uptobox.com/abcdefghijk1",
+        ])
+        .await;
+    assert!(!output.status.success());
+    assert!(env.local().join("some.show.s01e01.mkv").exists());
+
+    fs::write(&mkvpropedit, original).unwrap();
+    env.run_ok(&[
+        "backup",
+        "show",
+        "My Show",
+        "https://uptobox.com/abcdefghijk2",
+    ])
+    .await;
+
+    // The file that was never uploaded is not counted as an upload
+    assert!(env.local().join("some.show.s01e01.mkv").exists());
+    assert!(env.local().join("some.show.s01e02.mkv").exists());
+    assert_eq!(mock.files_in("//Shows").len(), 1);
+}
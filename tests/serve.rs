@@ -0,0 +1,131 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use common::{content, free_port, DavServer, MockServer, TestEnv};
+use reqwest::{header, redirect::Policy, Client, Method, StatusCode};
+use std::time::Duration;
+
+/// Wait for the share to answer
+async fn wait(client: &Client, url: &str) {
+    for _ in 0..50 {
+        let propfind = Method::from_bytes(b"PROPFIND").unwrap();
+        if client.request(propfind, url).send().await.is_ok() {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("The share did not start");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn serve_webdav_lists_and_proxies_ranges() {
+    let mock = MockServer::start().await;
+    let data = content(64 * 1024);
+    mock.add_folder("//Shows/My Show");
+    mock.add_file("abcdefghijkl", "ep.mkv", "//Shows/My Show", data.clone());
+    let env = TestEnv::new(&mock, "//Shows");
+
+    let address = format!("127.0.0.1:{}", free_port());
+    let _server = env.spawn(&["serve-webdav", "--address", &address]);
+    let client = Client::new();
+    let url = format!("http://{address}");
+    wait(&client, &url).await;
+
+    // Folders are listed from the remote hierarchy
+    let res = client
+        .request(
+            Method::from_bytes(b"PROPFIND").unwrap(),
+            format!("{url}/Shows/"),
+        )
+        .header("Depth", "1")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::MULTI_STATUS);
+    assert!(res.text().await.unwrap().contains("My%20Show"));
+
+    // Ranges are proxied from the download link
+    let res = client
+        .get(format!("{url}/Shows/My%20Show/ep.mkv"))
+        .header(header::RANGE, "bytes=1000-1999")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(res.bytes().await.unwrap().as_ref(), &data[1000..2000]);
+
+    // The share is read-only
+    let res = client
+        .put(format!("{url}/Shows/new.mkv"))
+        .body("data")
+        .send()
+        .await
+        .unwrap();
+    assert!(!res.status().is_success());
+    assert!(mock.files_in("//Shows").is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn serve_webdav_redirects_downloads() {
+    let mock = MockServer::start().await;
+    mock.add_file("abcdefghijkl", "film.mkv", "//", content(1024));
+    let env = TestEnv::new(&mock, "//");
+
+    let address = format!("127.0.0.1:{}", free_port());
+    let _server = env.spawn(&["serve-webdav", "--address", &address, "--redirect"]);
+    let client = Client::builder().redirect(Policy::none()).build().unwrap();
+    let url = format!("http://{address}");
+    wait(&client, &url).await;
+
+    let res = client.get(format!("{url}/film.mkv")).send().await.unwrap();
+    assert_eq!(res.status(), StatusCode::FOUND);
+    let location = res.headers()[header::LOCATION].to_str().unwrap();
+    assert!(location.ends_with("/dl/abcdefghijkl"), "{location}");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn serve_webdav_refuses_to_redirect_to_private_links() {
+    let dav = DavServer::start_with_login().await;
+    let mock = MockServer::start().await;
+    let env = TestEnv::new(&mock, "//");
+    env.configure(&format!(
+        "\n[storage]\nbackend = \"webdav\"\nurl = \"{}\"\nusername = \"user\"\npassword = \"secret\"",
+        dav.url()
+    ));
+
+    let address = format!("127.0.0.1:{}", free_port());
+    let output = env
+        .run(&["serve-webdav", "--address", &address, "--redirect"])
+        .await;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Unable to redirect the downloads"),
+        "{stderr}"
+    );
+    assert!(!stderr.contains("secret"), "{stderr}");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn serve_webdav_stops_at_the_first_interrupt() {
+    let mock = MockServer::start().await;
+    let env = TestEnv::new(&mock, "//");
+
+    let address = format!("127.0.0.1:{}", free_port());
+    let mut server = env.spawn(&["serve-webdav", "--address", &address]);
+    wait(&Client::new(), &format!("http://{address}")).await;
+
+    // No stage is running, a single Ctrl-C is enough
+    let pid = server.id().unwrap().to_string();
+    let kill = std::process::Command::new("kill")
+        .args(["-INT", &pid])
+        .status()
+        .unwrap();
+    assert!(kill.success());
+    let status = tokio::time::timeout(Duration::from_secs(5), server.wait())
+        .await
+        .expect("The share did not stop")
+        .unwrap();
+    assert_eq!(status.code(), Some(130));
+}
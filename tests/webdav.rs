@@ -0,0 +1,75 @@
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use common::{content, DavServer, MockServer, TestEnv};
+use std::fs;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn backup_show_to_webdav_share() {
+    let dav = DavServer::start_with_login().await;
+    let data = content(3 * 1024 * 1024);
+    fs::write(dav.dir.path().join("some.show.s01e02.mkv"), &data).unwrap();
+    fs::create_dir_all(dav.dir.path().join("Shows/My Show")).unwrap();
+
+    let mock = MockServer::start().await;
+    let env = TestEnv::new(&mock, "//Shows/My Show");
+    env.configure(&format!(
+        "\n[storage]\nbackend = \"webdav\"\nurl = \"{}\"\nusername = \"user\"\npassword = \"secret\"",
+        dav.url()
+    ));
+
+    env.run_ok(&[
+        "backup",
+        "show",
+        "My Show",
+        "//some.show.s01e02.mkv",
+        "-s",
+        "web",
+    ])
+    .await;
+
+    // The renamed upload is moved to the destination of the share
+    let uploaded = dav
+        .dir
+        .path()
+        .join("Shows/My Show/My.Show.s01e02.1080p.WEBDL.EAC3.h264.mkv");
+    assert_eq!(fs::read(uploaded).unwrap(), data);
+    assert_eq!(
+        fs::read(env.local().join("some.show.s01e02.mkv")).unwrap(),
+        data
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn remote_commands_on_webdav_share() {
+    let dav = DavServer::start().await;
+    fs::write(dav.dir.path().join("a.mkv"), content(1024)).unwrap();
+
+    let mock = MockServer::start().await;
+    let env = TestEnv::new(&mock, "//");
+    env.configure(&format!(
+        "\n[storage]\nbackend = \"webdav\"\nurl = \"{}\"",
+        dav.url()
+    ));
+
+    for args in [
+        &["remote", "mkdir", "-p", "//Films/Old"][..],
+        &["remote", "mv", "//a.mkv", "//Films"],
+        &["remote", "rename", "//Films/a.mkv", "b.mkv"],
+        &["remote", "rm", "-r", "//Films/Old"],
+    ] {
+        let output = env.run(args).await;
+        assert!(
+            output.status.success(),
+            "{args:?}: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    assert!(dav.dir.path().join("Films/b.mkv").exists());
+    assert!(!dav.dir.path().join("Films/Old").exists());
+
+    let output = env.run(&["remote", "ls", "-R", "--json", "//"]).await;
+    let listing = String::from_utf8_lossy(&output.stdout);
+    assert!(listing.contains("//Films/b.mkv"), "{listing}");
+}